@@ -0,0 +1,59 @@
+-- Timestamps are milliseconds since the Unix epoch, which both SQLite and PostgreSQL store the same way
+
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL,
+    password TEXT NOT NULL,
+    verified_at BIGINT,
+    -- Comma separated role names
+    roles TEXT NOT NULL,
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    totp_pending_secret TEXT,
+    totp_secret TEXT
+);
+
+CREATE UNIQUE INDEX users_email_unique ON users (LOWER(email));
+CREATE INDEX users_created_at ON users (created_at, id);
+
+CREATE TABLE recovery_codes (
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    refresh_token_hash TEXT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE INDEX sessions_user_id ON sessions (user_id);
+CREATE INDEX sessions_refresh_token_hash ON sessions (refresh_token_hash);
+
+CREATE TABLE previous_refresh_tokens (
+    refresh_token_hash TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL REFERENCES sessions (id) ON DELETE CASCADE
+);
+
+CREATE TABLE one_time_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    purpose TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX one_time_tokens_user_purpose ON one_time_tokens (user_id, purpose);
+CREATE INDEX one_time_tokens_token_hash ON one_time_tokens (purpose, token_hash);
+
+CREATE TABLE sign_in_attempts (
+    subject TEXT PRIMARY KEY,
+    failures BIGINT NOT NULL,
+    locked_until BIGINT
+);
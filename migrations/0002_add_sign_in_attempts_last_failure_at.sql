@@ -0,0 +1,2 @@
+-- Lets failures older than the lockout's window be forgotten. Existing rows start over.
+ALTER TABLE sign_in_attempts ADD COLUMN last_failure_at BIGINT;
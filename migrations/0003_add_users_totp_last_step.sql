@@ -0,0 +1,2 @@
+-- Time step of the last TOTP code accepted, so no code is accepted twice
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
@@ -0,0 +1,3 @@
+-- Refresh tokens identify a single session, as the unique Mongo index already enforces
+DROP INDEX sessions_refresh_token_hash;
+CREATE UNIQUE INDEX sessions_refresh_token_hash_unique ON sessions (refresh_token_hash);
@@ -0,0 +1,376 @@
+use std::fmt;
+
+use async_graphql::{Error, ErrorExtensions};
+
+use crate::domain::user::{
+    admin_create_user, change_password, confirm_totp, delete_user, disable_user, enable_user,
+    enroll_totp, find_one, force_password_reset, list_users, refresh_session, register,
+    reset_password, set_roles, sign_in, sign_out, sign_out_everywhere, unlock_account,
+    verify_email, verify_totp,
+};
+
+/// Set as `extensions.code` on every error so clients don't have to match on messages.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    NotFound,
+    BadUserInput,
+    Unauthenticated,
+    Forbidden,
+    Conflict,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::BadUserInput => "BAD_USER_INPUT",
+            ErrorCode::Unauthenticated => "UNAUTHENTICATED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+}
+
+/// An error as clients see it, turned into a GraphQL error with `extend`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: &'static str,
+    /// Argument the error is about, set as `extensions.field`
+    pub field: Option<&'static str>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: &'static str) -> Self {
+        Self {
+            code,
+            message,
+            field: None,
+        }
+    }
+
+    pub fn on_field(self, field: &'static str) -> Self {
+        Self {
+            field: Some(field),
+            ..self
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(ErrorCode::NotFound, "Not Found")
+    }
+
+    pub fn unauthenticated() -> Self {
+        Self::new(ErrorCode::Unauthenticated, "Unauthenticated")
+    }
+
+    pub fn forbidden() -> Self {
+        Self::new(ErrorCode::Forbidden, "Forbidden")
+    }
+
+    /// Details stay in the server logs rather than being sent to clients.
+    pub fn internal() -> Self {
+        Self::new(ErrorCode::Internal, "Unknown Error")
+    }
+
+    fn invalid_password(field: &'static str) -> Self {
+        Self::new(ErrorCode::BadUserInput, "Invalid Password").on_field(field)
+    }
+
+    fn invalid_token() -> Self {
+        Self::new(ErrorCode::BadUserInput, "Invalid Token").on_field("token")
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl ErrorExtensions for ApiError {
+    fn extend(&self) -> Error {
+        Error::new(self.message).extend_with(|_, extensions| {
+            extensions.set("code", self.code.as_str());
+            if let Some(field) = self.field {
+                extensions.set("field", field);
+            }
+        })
+    }
+}
+
+impl From<find_one::FindOneError> for ApiError {
+    fn from(error: find_one::FindOneError) -> Self {
+        match error {
+            find_one::FindOneError::NotFound => ApiError::not_found(),
+            find_one::FindOneError::InvalidId => {
+                ApiError::new(ErrorCode::BadUserInput, "Invalid Input").on_field("id")
+            }
+            find_one::FindOneError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<list_users::ListUsersError> for ApiError {
+    fn from(error: list_users::ListUsersError) -> Self {
+        match error {
+            list_users::ListUsersError::FirstAndLast => {
+                ApiError::new(ErrorCode::BadUserInput, "Invalid Input")
+            }
+            list_users::ListUsersError::InvalidCursor => {
+                ApiError::new(ErrorCode::BadUserInput, "Invalid Cursor")
+            }
+            list_users::ListUsersError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<register::RegisterError> for ApiError {
+    fn from(error: register::RegisterError) -> Self {
+        match error {
+            register::RegisterError::AlreadyExists => {
+                ApiError::new(ErrorCode::Conflict, "Already Exists").on_field("username")
+            }
+            register::RegisterError::InvalidPassword => ApiError::invalid_password("password"),
+            register::RegisterError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<sign_in::SignInError> for ApiError {
+    fn from(error: sign_in::SignInError) -> Self {
+        match error {
+            // Reported like any other failure so locks don't reveal which accounts exist
+            sign_in::SignInError::Failed | sign_in::SignInError::Locked => {
+                ApiError::new(ErrorCode::Unauthenticated, "Login Failed")
+            }
+            sign_in::SignInError::Unverified => {
+                ApiError::new(ErrorCode::Forbidden, "Email Not Verified")
+            }
+            sign_in::SignInError::Disabled => {
+                ApiError::new(ErrorCode::Forbidden, "Account Disabled")
+            }
+            sign_in::SignInError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<verify_totp::VerifyTotpError> for ApiError {
+    fn from(error: verify_totp::VerifyTotpError) -> Self {
+        match error {
+            verify_totp::VerifyTotpError::InvalidChallenge => {
+                ApiError::new(ErrorCode::Unauthenticated, "Invalid Challenge").on_field("challenge")
+            }
+            verify_totp::VerifyTotpError::InvalidCode => {
+                ApiError::new(ErrorCode::Unauthenticated, "Invalid Code").on_field("code")
+            }
+            verify_totp::VerifyTotpError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<enroll_totp::EnrollTotpError> for ApiError {
+    fn from(error: enroll_totp::EnrollTotpError) -> Self {
+        match error {
+            enroll_totp::EnrollTotpError::NotFound => ApiError::not_found(),
+            enroll_totp::EnrollTotpError::AlreadyEnabled => {
+                ApiError::new(ErrorCode::Conflict, "Two Factor Already Enabled")
+            }
+            enroll_totp::EnrollTotpError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<confirm_totp::ConfirmTotpError> for ApiError {
+    fn from(error: confirm_totp::ConfirmTotpError) -> Self {
+        match error {
+            confirm_totp::ConfirmTotpError::NotFound => ApiError::not_found(),
+            confirm_totp::ConfirmTotpError::NotEnrolled => {
+                ApiError::new(ErrorCode::Conflict, "Two Factor Not Enrolled")
+            }
+            confirm_totp::ConfirmTotpError::InvalidCode => {
+                ApiError::new(ErrorCode::BadUserInput, "Invalid Code").on_field("code")
+            }
+            confirm_totp::ConfirmTotpError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<refresh_session::RefreshSessionError> for ApiError {
+    fn from(error: refresh_session::RefreshSessionError) -> Self {
+        match error {
+            refresh_session::RefreshSessionError::Invalid
+            | refresh_session::RefreshSessionError::Reused => {
+                ApiError::new(ErrorCode::Unauthenticated, "Invalid Refresh Token")
+            }
+            refresh_session::RefreshSessionError::Expired => {
+                ApiError::new(ErrorCode::Unauthenticated, "Refresh Token Expired")
+            }
+            refresh_session::RefreshSessionError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<sign_out::SignOutError> for ApiError {
+    fn from(error: sign_out::SignOutError) -> Self {
+        match error {
+            sign_out::SignOutError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<sign_out_everywhere::SignOutEverywhereError> for ApiError {
+    fn from(error: sign_out_everywhere::SignOutEverywhereError) -> Self {
+        match error {
+            sign_out_everywhere::SignOutEverywhereError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<change_password::ChangePasswordError> for ApiError {
+    fn from(error: change_password::ChangePasswordError) -> Self {
+        match error {
+            change_password::ChangePasswordError::NotFound => ApiError::not_found(),
+            change_password::ChangePasswordError::IncorrectPassword => {
+                ApiError::new(ErrorCode::BadUserInput, "Incorrect Password")
+                    .on_field("currentPassword")
+            }
+            change_password::ChangePasswordError::InvalidPassword => {
+                ApiError::invalid_password("newPassword")
+            }
+            change_password::ChangePasswordError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<reset_password::ResetPasswordError> for ApiError {
+    fn from(error: reset_password::ResetPasswordError) -> Self {
+        match error {
+            reset_password::ResetPasswordError::InvalidToken => ApiError::invalid_token(),
+            reset_password::ResetPasswordError::InvalidPassword => {
+                ApiError::invalid_password("newPassword")
+            }
+            reset_password::ResetPasswordError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<verify_email::VerifyEmailError> for ApiError {
+    fn from(error: verify_email::VerifyEmailError) -> Self {
+        match error {
+            verify_email::VerifyEmailError::InvalidToken => ApiError::invalid_token(),
+            verify_email::VerifyEmailError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<admin_create_user::AdminCreateUserError> for ApiError {
+    fn from(error: admin_create_user::AdminCreateUserError) -> Self {
+        match error {
+            admin_create_user::AdminCreateUserError::AlreadyExists => {
+                ApiError::new(ErrorCode::Conflict, "Already Exists").on_field("email")
+            }
+            admin_create_user::AdminCreateUserError::InvalidPassword => {
+                ApiError::invalid_password("password")
+            }
+            admin_create_user::AdminCreateUserError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<set_roles::SetRolesError> for ApiError {
+    fn from(error: set_roles::SetRolesError) -> Self {
+        match error {
+            set_roles::SetRolesError::NotFound => ApiError::not_found(),
+            set_roles::SetRolesError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<disable_user::DisableUserError> for ApiError {
+    fn from(error: disable_user::DisableUserError) -> Self {
+        match error {
+            disable_user::DisableUserError::NotFound => ApiError::not_found(),
+            disable_user::DisableUserError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<enable_user::EnableUserError> for ApiError {
+    fn from(error: enable_user::EnableUserError) -> Self {
+        match error {
+            enable_user::EnableUserError::NotFound => ApiError::not_found(),
+            enable_user::EnableUserError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<force_password_reset::ForcePasswordResetError> for ApiError {
+    fn from(error: force_password_reset::ForcePasswordResetError) -> Self {
+        match error {
+            force_password_reset::ForcePasswordResetError::NotFound => ApiError::not_found(),
+            force_password_reset::ForcePasswordResetError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<delete_user::DeleteUserError> for ApiError {
+    fn from(error: delete_user::DeleteUserError) -> Self {
+        match error {
+            delete_user::DeleteUserError::NotFound => ApiError::not_found(),
+            delete_user::DeleteUserError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+impl From<unlock_account::UnlockAccountError> for ApiError {
+    fn from(error: unlock_account::UnlockAccountError) -> Self {
+        match error {
+            unlock_account::UnlockAccountError::NotFound => ApiError::not_found(),
+            unlock_account::UnlockAccountError::Unknown => ApiError::internal(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Pos, Value};
+
+    use super::*;
+
+    fn extensions(error: ApiError) -> Value {
+        let error = error.extend().into_server_error(Pos::default());
+        serde_json::from_value(serde_json::to_value(error.extensions).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn should_set_code_and_field() {
+        let error = ApiError::from(register::RegisterError::InvalidPassword);
+
+        assert_eq!(error.to_string(), "Invalid Password");
+        assert_eq!(
+            extensions(error),
+            async_graphql::value!({ "code": "BAD_USER_INPUT", "field": "password" })
+        );
+    }
+
+    #[test]
+    fn should_leave_out_field_when_not_about_an_argument() {
+        let error = ApiError::from(find_one::FindOneError::NotFound);
+
+        assert_eq!(
+            extensions(error),
+            async_graphql::value!({ "code": "NOT_FOUND" })
+        );
+    }
+
+    #[test]
+    fn should_not_tell_locked_accounts_apart() {
+        assert_eq!(
+            ApiError::from(sign_in::SignInError::Locked),
+            ApiError::from(sign_in::SignInError::Failed)
+        );
+    }
+}
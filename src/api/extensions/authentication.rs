@@ -1,25 +1,251 @@
 use std::sync::Arc;
 
-use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextRequest};
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    ErrorExtensionValues, Request, ServerError, ServerResult,
+};
 
-pub struct Authentication;
+use crate::{api::error::ErrorCode, domain::user::access_token, repositories::session};
+
+/// The caller resolved from a valid bearer token.
+pub struct AuthenticatedUser {
+    pub id: String,
+    pub session_id: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuthenticationError {
+    InvalidHeader,
+    InvalidToken,
+    ExpiredToken,
+    RevokedSession,
+    Unknown,
+}
+
+impl AuthenticationError {
+    fn into_server_error(self) -> ServerError {
+        let (message, code) = match self {
+            AuthenticationError::InvalidHeader => {
+                ("Invalid Authorization Header", ErrorCode::Unauthenticated)
+            }
+            AuthenticationError::InvalidToken => ("Invalid Token", ErrorCode::Unauthenticated),
+            AuthenticationError::ExpiredToken => ("Token Expired", ErrorCode::Unauthenticated),
+            AuthenticationError::RevokedSession => ("Session Revoked", ErrorCode::Unauthenticated),
+            AuthenticationError::Unknown => ("Unknown Error", ErrorCode::Internal),
+        };
+
+        let mut extensions = ErrorExtensionValues::default();
+        extensions.set("code", code.as_str());
+
+        let mut error = ServerError::new(message, None);
+        error.extensions = Some(extensions);
+        error
+    }
+}
+
+/// Attaches the caller identified by an `Authorization: Bearer` header to the request.
+///
+/// async-graphql drops data added to a request once extensions start running, so the token is
+/// checked while the request is built and any failure is left for [`Authentication`] to report.
+/// Whether the token's session is still active is checked by the extension itself.
+pub fn authenticate(
+    request: Request,
+    keys: &access_token::Keys,
+    authorization: Option<String>,
+) -> Request {
+    let authorization = match authorization {
+        Some(authorization) => authorization,
+        None => return request,
+    };
+
+    let token = match authorization.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+        _ => return request.data(AuthenticationError::InvalidHeader),
+    };
+
+    match access_token::verify(keys, token) {
+        Ok(claims) => request.data(AuthenticatedUser {
+            id: claims.sub,
+            session_id: claims.sid,
+        }),
+        Err(access_token::VerifyError::Expired) => request.data(AuthenticationError::ExpiredToken),
+        Err(access_token::VerifyError::Invalid) => request.data(AuthenticationError::InvalidToken),
+    }
+}
+
+pub struct Authentication {
+    sessions: Arc<dyn session::Repository>,
+}
+
+impl Authentication {
+    pub fn new(sessions: Arc<dyn session::Repository>) -> Self {
+        Self { sessions }
+    }
+}
 
 impl ExtensionFactory for Authentication {
     fn create(&self) -> Arc<dyn Extension> {
-        Arc::new(AuthenticationExtension)
+        Arc::new(AuthenticationExtension {
+            sessions: self.sessions.clone(),
+        })
     }
 }
 
-struct AuthenticationExtension;
+struct AuthenticationExtension {
+    sessions: Arc<dyn session::Repository>,
+}
+
+impl AuthenticationExtension {
+    async fn check_session(&self, caller: &AuthenticatedUser) -> Result<(), AuthenticationError> {
+        let result = self.sessions.find_by_id(caller.session_id.clone()).await;
+
+        match result {
+            Ok(session) if session.revoked || session.user_id != caller.id => {
+                Err(AuthenticationError::RevokedSession)
+            }
+            Ok(_) => Ok(()),
+            Err(session::FindByIdError::NotFound) | Err(session::FindByIdError::InvalidId) => {
+                Err(AuthenticationError::RevokedSession)
+            }
+            Err(err @ session::FindByIdError::Database(_)) => {
+                log::error!("Error In authentication: {}", err);
+                Err(AuthenticationError::Unknown)
+            }
+        }
+    }
+}
 
 #[async_trait::async_trait]
 impl Extension for AuthenticationExtension {
-    async fn request(
+    async fn prepare_request(
         &self,
         ctx: &ExtensionContext<'_>,
-        next: NextRequest<'_>,
-    ) -> async_graphql::Response {
-        let header = warp::header::<String>("authorization");
-        next.run(ctx).await
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if let Some(error) = ctx.data_opt::<AuthenticationError>() {
+            return Err(error.into_server_error());
+        }
+
+        if let Some(caller) = ctx.data_opt::<AuthenticatedUser>() {
+            self.check_session(caller)
+                .await
+                .map_err(AuthenticationError::into_server_error)?;
+        }
+
+        next.run(ctx, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+    use chrono::{Duration, Utc};
+
+    use crate::{domain::user::entities::Session, repositories::session::MockRepository};
+
+    use super::*;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn caller(&self, ctx: &Context<'_>) -> Option<String> {
+            ctx.data_opt::<AuthenticatedUser>()
+                .map(|user| user.id.clone())
+        }
+    }
+
+    fn sessions(revoked: bool) -> Arc<MockRepository> {
+        let mut sessions = MockRepository::new();
+        sessions.expect_find_by_id().returning(move |id| {
+            Ok(Session {
+                id,
+                user_id: "id".to_string(),
+                refresh_token_hash: "hash".to_string(),
+                revoked,
+                expires_at: Utc::now() + Duration::days(1),
+            })
+        });
+
+        Arc::new(sessions)
+    }
+
+    async fn execute(
+        keys: &access_token::Keys,
+        sessions: Arc<MockRepository>,
+        authorization: Option<String>,
+    ) -> serde_json::Value {
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(Authentication::new(sessions))
+            .finish();
+
+        let request = authenticate(Request::new("{ caller }"), keys, authorization);
+
+        serde_json::to_value(schema.execute(request).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_resolve_caller_from_bearer_token() {
+        let keys = access_token::Keys::from_secret(b"secret", Duration::minutes(15));
+        let token = access_token::issue(&keys, "id", "session").unwrap().token;
+
+        let response = execute(&keys, sessions(false), Some(format!("Bearer {}", token))).await;
+
+        assert_eq!(response["data"]["caller"], "id");
+    }
+
+    #[tokio::test]
+    async fn should_reject_token_for_revoked_session() {
+        let keys = access_token::Keys::from_secret(b"secret", Duration::minutes(15));
+        let token = access_token::issue(&keys, "id", "session").unwrap().token;
+
+        let response = execute(&keys, sessions(true), Some(format!("Bearer {}", token))).await;
+
+        assert_eq!(response["errors"][0]["message"], "Session Revoked");
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "UNAUTHENTICATED"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_leave_anonymous_requests_unauthenticated() {
+        let keys = access_token::Keys::from_secret(b"secret", Duration::minutes(15));
+
+        let response = execute(&keys, sessions(false), None).await;
+
+        assert_eq!(response["data"]["caller"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn should_reject_expired_token() {
+        let keys = access_token::Keys::from_secret(b"secret", Duration::minutes(-5));
+        let token = access_token::issue(&keys, "id", "session").unwrap().token;
+
+        let response = execute(&keys, sessions(false), Some(format!("Bearer {}", token))).await;
+
+        assert_eq!(response["errors"][0]["message"], "Token Expired");
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "UNAUTHENTICATED"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_non_bearer_header() {
+        let keys = access_token::Keys::from_secret(b"secret", Duration::minutes(15));
+
+        let response = execute(
+            &keys,
+            sessions(false),
+            Some("Basic dXNlcjpwYXNz".to_string()),
+        )
+        .await;
+
+        assert_eq!(
+            response["errors"][0]["message"],
+            "Invalid Authorization Header"
+        );
     }
 }
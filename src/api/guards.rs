@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use async_graphql::{Context, ErrorExtensions, Guard, Result};
+
+use crate::{
+    api::{error::ApiError, extensions::authentication::AuthenticatedUser},
+    domain::user::{entities::Role, find_one},
+    repositories::user::Repository as UserRepository,
+};
+
+/// Only lets callers holding `role` through.
+///
+/// Roles are read from the user record on every check rather than the access token, so
+/// taking a role away applies immediately.
+pub struct RoleGuard {
+    role: Role,
+}
+
+impl RoleGuard {
+    pub fn new(role: Role) -> Self {
+        Self { role }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let caller = ctx
+            .data_opt::<AuthenticatedUser>()
+            .ok_or_else(|| ApiError::unauthenticated().extend())?;
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+
+        let result = find_one::execute(repo.clone(), caller.id.clone()).await;
+
+        match result {
+            Ok(user) if user.roles.contains(&self.role) => Ok(()),
+            Ok(_)
+            | Err(find_one::FindOneError::NotFound)
+            | Err(find_one::FindOneError::InvalidId) => Err(ApiError::forbidden().extend()),
+            Err(find_one::FindOneError::Unknown) => Err(ApiError::internal().extend()),
+        }
+    }
+}
+
+/// Only lets the user identified by `user_id` through.
+pub struct SelfGuard {
+    user_id: String,
+}
+
+impl SelfGuard {
+    pub fn new(user_id: &str) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for SelfGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<AuthenticatedUser>() {
+            Some(caller) if caller.id == self.user_id => Ok(()),
+            Some(_) => Err(ApiError::forbidden().extend()),
+            None => Err(ApiError::unauthenticated().extend()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema};
+
+    use super::*;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        #[graphql(guard = "SelfGuard::new(&id)")]
+        async fn secret(&self, id: String) -> String {
+            id
+        }
+    }
+
+    async fn execute(caller: Option<&str>) -> serde_json::Value {
+        let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+
+        let mut request = Request::new(r#"{ secret(id: "id") }"#);
+        if let Some(caller) = caller {
+            request = request.data(AuthenticatedUser {
+                id: caller.to_string(),
+                session_id: "session".to_string(),
+            });
+        }
+
+        serde_json::to_value(schema.execute(request).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_allow_user_themselves() {
+        let response = execute(Some("id")).await;
+
+        assert_eq!(response["data"]["secret"], "id");
+    }
+
+    #[tokio::test]
+    async fn should_forbid_other_users() {
+        let response = execute(Some("other")).await;
+
+        assert_eq!(response["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    }
+
+    #[tokio::test]
+    async fn should_reject_anonymous_callers() {
+        let response = execute(None).await;
+
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "UNAUTHENTICATED"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{convert::Infallible, sync::Arc};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
 
 use async_graphql::{
     http::{playground_source, GraphQLPlaygroundConfig},
@@ -10,27 +10,82 @@ use warp::{
     Reply,
 };
 
-use crate::repositories::user::MongoRepository;
+use crate::{
+    domain::{
+        clock::Clock,
+        user::{access_token, hash_password, sign_in},
+    },
+    mailer::Mailer,
+    repositories::Repositories,
+};
 
+mod error;
 mod extensions;
+mod guards;
+pub mod rate_limit;
 mod routes;
 mod schema;
 
-pub fn make_routes(repo: Arc<MongoRepository>) -> BoxedFilter<(impl Reply,)> {
-    let schema = schema::build_schema()
-        .data(repo)
-        .extension(extensions::authentication::Authentication)
-        .finish();
+/// Everything resolvers work with, whichever store and mailer they are backed by.
+pub struct Services {
+    pub repositories: Repositories,
+    pub mailer: Arc<dyn Mailer>,
+    pub keys: Arc<access_token::Keys>,
+    pub password_hashing: Arc<hash_password::Params>,
+    pub sign_in_settings: Arc<sign_in::Settings>,
+    pub clock: Arc<dyn Clock>,
+}
+
+pub fn make_routes(
+    services: Services,
+    rate_limiter: Arc<rate_limit::RateLimiter>,
+) -> BoxedFilter<(impl Reply,)> {
+    let keys = services.keys.clone();
+    let schema = schema::build_schema(services).finish();
 
     let health = warp::path::end().and_then(routes::health);
 
-    let graphql_handler = warp::post().and(warp::path("graphql").and(
-        async_graphql_warp::graphql(schema).and_then(
-            |(schema, request): (Schema<_, _, _>, Request)| async move {
-                Ok::<_, Infallible>(GraphQLResponse::from(schema.execute(request).await))
-            },
+    let graphql_handler = warp::post().and(
+        warp::path("graphql").and(
+            async_graphql_warp::graphql(schema)
+                .and(warp::header::optional::<String>("authorization"))
+                .and(warp::addr::remote())
+                .and(warp::header::optional::<String>("x-forwarded-for"))
+                .and_then(
+                    move |(schema, request): (Schema<_, _, _>, Request),
+                          authorization: Option<String>,
+                          remote: Option<SocketAddr>,
+                          forwarded_for: Option<String>| {
+                        let keys = keys.clone();
+                        let rate_limiter = rate_limiter.clone();
+
+                        async move {
+                            // There's no peer address only when not served over TCP, as in tests
+                            if let Some(client) =
+                                rate_limiter.client_ip(remote, forwarded_for.as_deref())
+                            {
+                                if let Err(limited) = rate_limiter.check(client, &request).await {
+                                    return Ok::<_, Infallible>(rate_limit::too_many_requests(
+                                        limited,
+                                    ));
+                                }
+                            }
+
+                            let request = extensions::authentication::authenticate(
+                                request,
+                                &keys,
+                                authorization,
+                            );
+
+                            Ok(
+                                GraphQLResponse::from(schema.execute(request).await)
+                                    .into_response(),
+                            )
+                        }
+                    },
+                ),
         ),
-    ));
+    );
 
     let graphql_playground = warp::get().and(warp::path("playground")).map(|| {
         HttpResponse::builder()
@@ -0,0 +1,196 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use super::{Limited, Quota, Store};
+
+/// Most buckets kept at once; past it the least recently used one is dropped for a new client
+const MAX_BUCKETS: usize = 100_000;
+
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn full(quota: Quota, now: Instant) -> Self {
+        Self {
+            quota,
+            tokens: quota.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    fn tokens_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        let rate = self.quota.capacity as f64 / self.quota.period.as_secs_f64();
+
+        (self.tokens + elapsed * rate).min(self.quota.capacity as f64)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        self.tokens = self.tokens_at(now);
+        self.updated_at = now;
+    }
+
+    fn take(&mut self, cost: u32, now: Instant) -> Result<(), Limited> {
+        self.refill(now);
+
+        if self.tokens >= cost as f64 {
+            self.tokens -= cost as f64;
+            return Ok(());
+        }
+
+        let rate = self.quota.capacity as f64 / self.quota.period.as_secs_f64();
+        Err(Limited::RetryAfter(Duration::from_secs_f64(
+            (cost as f64 - self.tokens) / rate,
+        )))
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    /// Every key by when its bucket was last used, oldest first
+    by_age: BTreeSet<(Instant, String)>,
+}
+
+/// Keeps buckets in this process, so every instance of the server limits on its own.
+pub struct MemoryStore {
+    buckets: Mutex<Buckets>,
+    max_buckets: usize,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::with_max_buckets(MAX_BUCKETS)
+    }
+
+    fn with_max_buckets(max_buckets: usize) -> Self {
+        Self {
+            buckets: Mutex::new(Buckets::default()),
+            max_buckets,
+        }
+    }
+
+    /// Drops idle buckets every `interval` on a background task, for as long as the store is used.
+    pub fn prune_every(self: &Arc<Self>, interval: Duration) {
+        let store = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                match store.upgrade() {
+                    Some(store) => store.prune(Instant::now()),
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Drops every bucket that has filled back up, which is the same as one never created.
+    fn prune(&self, now: Instant) {
+        let Buckets { by_key, by_age } = &mut *self.buckets.lock().unwrap();
+
+        by_key.retain(|key, bucket| {
+            if bucket.tokens_at(now) < bucket.quota.capacity as f64 {
+                return true;
+            }
+
+            by_age.remove(&(bucket.updated_at, key.clone()));
+            false
+        });
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn take(&self, key: String, quota: Quota, cost: u32) -> Result<(), Limited> {
+        let now = Instant::now();
+        let Buckets { by_key, by_age } = &mut *self.buckets.lock().unwrap();
+
+        let bucket = match by_key.get_mut(&key) {
+            Some(bucket) => {
+                by_age.remove(&(bucket.updated_at, key.clone()));
+                bucket
+            }
+            None => {
+                if by_key.len() >= self.max_buckets {
+                    if let Some((_, oldest)) = by_age.pop_first() {
+                        by_key.remove(&oldest);
+                    }
+                }
+
+                by_key
+                    .entry(key.clone())
+                    .or_insert_with(|| Bucket::full(quota, now))
+            }
+        };
+
+        let results = bucket.take(cost, now);
+        by_age.insert((bucket.updated_at, key));
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota() -> Quota {
+        Quota {
+            capacity: 2,
+            period: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn should_refill_bucket_over_period() {
+        let start = Instant::now();
+        let mut bucket = Bucket::full(quota(), start);
+
+        assert_eq!(bucket.take(2, start), Ok(()));
+        assert_eq!(
+            bucket.take(1, start),
+            Err(Limited::RetryAfter(Duration::from_secs(5)))
+        );
+        assert_eq!(bucket.take(1, start + Duration::from_secs(5)), Ok(()));
+        assert!(bucket.take(1, start + Duration::from_secs(6)).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_drop_least_recently_used_bucket_when_full() {
+        let store = MemoryStore::with_max_buckets(2);
+
+        assert!(store.take("a".to_string(), quota(), 2).await.is_ok());
+        assert!(store.take("b".to_string(), quota(), 2).await.is_ok());
+        assert!(store.take("a".to_string(), quota(), 0).await.is_ok());
+        assert!(store.take("c".to_string(), quota(), 2).await.is_ok());
+
+        let buckets = store.buckets.lock().unwrap();
+        assert!(buckets.by_key.contains_key("a"));
+        assert!(!buckets.by_key.contains_key("b"));
+        assert_eq!(buckets.by_key.len(), buckets.by_age.len());
+    }
+
+    #[tokio::test]
+    async fn should_prune_buckets_that_have_refilled() {
+        let store = MemoryStore::new();
+
+        assert!(store.take("a".to_string(), quota(), 1).await.is_ok());
+        store.prune(Instant::now());
+        assert_eq!(store.buckets.lock().unwrap().by_key.len(), 1);
+
+        store.prune(Instant::now() + Duration::from_secs(10));
+        let buckets = store.buckets.lock().unwrap();
+        assert!(buckets.by_key.is_empty());
+        assert!(buckets.by_age.is_empty());
+    }
+}
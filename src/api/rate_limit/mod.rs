@@ -0,0 +1,387 @@
+mod memory;
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_graphql::{
+    parser::{
+        parse_query,
+        types::{ExecutableDocument, OperationType, Selection, SelectionSet},
+    },
+    Request,
+};
+use async_trait::async_trait;
+use serde_json::json;
+use warp::{http::StatusCode, reply::Response, Reply};
+
+pub use memory::MemoryStore;
+
+/// Mutations that can be used to guess credentials or send emails, which get their own stricter
+/// bucket per client
+const SENSITIVE_OPERATIONS: [&str; 6] = [
+    "signIn",
+    "verifyTotp",
+    "register",
+    "requestPasswordReset",
+    "resendVerification",
+    "resetPassword",
+];
+
+/// How often the buckets kept in memory are swept for ones that have filled back up
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A bucket holding up to `capacity` tokens, refilled evenly over `period`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Quota {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub fn per_minute(capacity: u32) -> Self {
+        Self {
+            capacity,
+            period: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Every GraphQL request from a client
+    pub requests: Quota,
+    /// Each of the sensitive mutations, counted separately for every client
+    pub sensitive_operations: Quota,
+    /// Proxies whose `X-Forwarded-For` header is believed when working out the client
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            requests: Quota::per_minute(120),
+            sensitive_operations: Quota::per_minute(10),
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+/// Why a request was turned away.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Limited {
+    /// The bucket will have refilled enough after this long
+    RetryAfter(Duration),
+    /// The request costs more than the bucket can ever hold, so retrying won't help
+    OverCapacity,
+}
+
+/// Where buckets are kept, so limits can be shared between instances by swapping the store.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Takes `cost` tokens from the bucket under `key`, or returns how long until enough have
+    /// refilled. `cost` is never more than the quota's capacity.
+    async fn take(&self, key: String, quota: Quota, cost: u32) -> Result<(), Limited>;
+}
+
+pub struct RateLimiter {
+    store: Arc<dyn Store>,
+    settings: Settings,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn Store>, settings: Settings) -> Self {
+        Self { store, settings }
+    }
+
+    /// The address requests are limited by: the peer itself, or the first address in
+    /// `X-Forwarded-For` not added by a trusted proxy when the peer is one.
+    pub fn client_ip(
+        &self,
+        remote: Option<SocketAddr>,
+        forwarded_for: Option<&str>,
+    ) -> Option<IpAddr> {
+        let mut client = remote?.ip();
+
+        let forwarded_for = match forwarded_for {
+            Some(forwarded_for) => forwarded_for,
+            None => return Some(client),
+        };
+
+        // Each proxy appends the address it received the request from, so walk back from the end
+        // for as long as the hop we're at is trusted to have told the truth
+        for hop in forwarded_for.rsplit(',') {
+            if !self.settings.trusted_proxies.contains(&client) {
+                break;
+            }
+
+            match hop.trim().parse() {
+                Ok(hop) => client = hop,
+                Err(_) => break,
+            }
+        }
+
+        Some(client)
+    }
+
+    /// Charges a request against the client's buckets, returning how long to wait when one of them
+    /// is empty.
+    pub async fn check(&self, client: IpAddr, request: &Request) -> Result<(), Limited> {
+        let client = client_key(client);
+        let operations = sensitive_operations(&request.query);
+
+        if operations
+            .values()
+            .any(|count| *count > self.settings.sensitive_operations.capacity)
+        {
+            return Err(Limited::OverCapacity);
+        }
+
+        self.store
+            .take(client.clone(), self.settings.requests, 1)
+            .await?;
+
+        for (operation, count) in operations {
+            self.store
+                .take(
+                    format!("{}:{}", client, operation),
+                    self.settings.sensitive_operations,
+                    count,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Who a bucket belongs to. IPv6 clients are counted by their /64, as each of them is usually
+/// handed the whole prefix and could otherwise just move to the next address.
+fn client_key(client: IpAddr) -> String {
+    let client = match client {
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(address) => IpAddr::V4(address),
+            None => {
+                let prefix = u128::from(address) & !(u128::MAX >> 64);
+                return format!("{}/64", Ipv6Addr::from(prefix));
+            }
+        },
+        client => client,
+    };
+
+    client.to_string()
+}
+
+/// The HTTP 429 sent instead of running a request, shaped like a GraphQL error response.
+///
+/// Only says when to retry if doing so can succeed.
+pub fn too_many_requests(limited: Limited) -> Response {
+    let body = json!({
+        "errors": [{
+            "message": "Too Many Requests",
+            "extensions": { "code": "RATE_LIMITED" },
+        }],
+    });
+
+    let response =
+        warp::reply::with_status(warp::reply::json(&body), StatusCode::TOO_MANY_REQUESTS);
+
+    let retry_after = match limited {
+        Limited::RetryAfter(retry_after) => retry_after,
+        Limited::OverCapacity => return response.into_response(),
+    };
+
+    // Rounded up, so retrying as soon as allowed finds the bucket refilled
+    let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    warp::reply::with_header(response, "retry-after", retry_after.max(1).to_string())
+        .into_response()
+}
+
+/// Counts every use of a sensitive mutation in the document, aliases included, so they can't be
+/// batched into one request to get around their bucket.
+fn sensitive_operations(query: &str) -> HashMap<&'static str, u32> {
+    let mut counts = HashMap::new();
+
+    // Documents that don't parse are rejected by the schema without running anything
+    let document = match parse_query(query) {
+        Ok(document) => document,
+        Err(_) => return counts,
+    };
+
+    for (_, operation) in document.operations.iter() {
+        if operation.node.ty == OperationType::Mutation {
+            count_fields(
+                &document,
+                &operation.node.selection_set.node,
+                &mut HashSet::new(),
+                &mut counts,
+            );
+        }
+    }
+
+    counts
+}
+
+fn count_fields<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited_fragments: &mut HashSet<&'a str>,
+    counts: &mut HashMap<&'static str, u32>,
+) {
+    for selection in &selection_set.items {
+        match &selection.node {
+            Selection::Field(field) => {
+                let name = field.node.name.node.as_str();
+                if let Some(operation) = SENSITIVE_OPERATIONS.iter().find(|op| **op == name) {
+                    *counts.entry(*operation).or_default() += 1;
+                }
+            }
+            Selection::InlineFragment(fragment) => count_fields(
+                document,
+                &fragment.node.selection_set.node,
+                visited_fragments,
+                counts,
+            ),
+            Selection::FragmentSpread(spread) => {
+                let name = spread.node.fragment_name.node.as_str();
+                if !visited_fragments.insert(name) {
+                    continue;
+                }
+
+                if let Some(fragment) = document.fragments.get(&spread.node.fragment_name.node) {
+                    count_fields(
+                        document,
+                        &fragment.node.selection_set.node,
+                        visited_fragments,
+                        counts,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(trusted_proxies: Vec<IpAddr>) -> RateLimiter {
+        RateLimiter::new(
+            Arc::new(MemoryStore::new()),
+            Settings {
+                requests: Quota::per_minute(5),
+                sensitive_operations: Quota::per_minute(2),
+                trusted_proxies,
+            },
+        )
+    }
+
+    #[test]
+    fn should_only_believe_forwarded_for_from_trusted_proxies() {
+        let proxy: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let limiter = limiter(vec![proxy.ip()]);
+
+        assert_eq!(
+            limiter.client_ip(Some(proxy), Some("1.1.1.1, 2.2.2.2")),
+            Some("2.2.2.2".parse().unwrap())
+        );
+        assert_eq!(
+            limiter.client_ip(Some("3.3.3.3:4000".parse().unwrap()), Some("2.2.2.2")),
+            Some("3.3.3.3".parse().unwrap())
+        );
+        assert_eq!(
+            limiter.client_ip(Some(proxy), Some("2.2.2.2, 10.0.0.1")),
+            Some("2.2.2.2".parse().unwrap())
+        );
+        assert_eq!(
+            limiter.client_ip(Some(proxy), Some("nonsense")),
+            Some(proxy.ip())
+        );
+    }
+
+    #[test]
+    fn should_count_aliased_and_fragment_sensitive_mutations() {
+        let counts = sensitive_operations(
+            r#"
+            mutation {
+                a: signIn(username: "a", password: "a") { __typename }
+                b: signIn(username: "b", password: "b") { __typename }
+                ...Reset
+                signOut
+            }
+            fragment Reset on Mutation { resetPassword(token: "t", newPassword: "p") }
+            "#,
+        );
+
+        assert_eq!(counts.get("signIn"), Some(&2));
+        assert_eq!(counts.get("resetPassword"), Some(&1));
+        assert_eq!(counts.get("signOut"), None);
+        assert!(sensitive_operations("{ signIn }").is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_limit_sensitive_mutations_separately() {
+        let limiter = limiter(vec![]);
+        let client: IpAddr = "1.1.1.1".parse().unwrap();
+        let sign_in =
+            Request::new(r#"mutation { signIn(username: "a", password: "a") { __typename } }"#);
+
+        assert!(limiter.check(client, &sign_in).await.is_ok());
+        assert!(limiter.check(client, &sign_in).await.is_ok());
+        assert!(limiter.check(client, &sign_in).await.is_err());
+        assert!(limiter
+            .check(client, &Request::new("{ me { id } }"))
+            .await
+            .is_ok());
+        assert!(limiter
+            .check("2.2.2.2".parse().unwrap(), &sign_in)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_limit_ipv6_clients_by_prefix() {
+        let limiter = limiter(vec![]);
+        let request = Request::new("{ me { id } }");
+
+        for host in 1..=5 {
+            let client: IpAddr = format!("2001:db8::{}", host).parse().unwrap();
+            assert!(limiter.check(client, &request).await.is_ok());
+        }
+
+        assert!(limiter
+            .check("2001:db8::ffff".parse().unwrap(), &request)
+            .await
+            .is_err());
+        assert!(limiter
+            .check("2001:db8:0:1::1".parse().unwrap(), &request)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_reject_requests_costing_more_than_capacity() {
+        let limiter = limiter(vec![]);
+        let request = Request::new(
+            r#"
+            mutation {
+                a: resendVerification(email: "a")
+                b: resendVerification(email: "b")
+                c: resendVerification(email: "c")
+            }
+            "#,
+        );
+
+        let results = limiter.check("1.1.1.1".parse().unwrap(), &request).await;
+
+        assert_eq!(results, Err(Limited::OverCapacity));
+        assert!(too_many_requests(Limited::OverCapacity)
+            .headers()
+            .get("retry-after")
+            .is_none());
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use crate::{
+    api::{error::ApiError, guards::RoleGuard},
+    domain::user::{
+        admin_create_user, delete_user, disable_user, enable_user,
+        entities::{self, Role},
+        force_password_reset, hash_password, set_roles, unlock_account,
+    },
+    mailer::Mailer,
+    repositories::{
+        one_time_token::Repository as OneTimeTokenRepository,
+        session::Repository as SessionRepository,
+        sign_in_attempt::Repository as SignInAttemptRepository, user::Repository as UserRepository,
+    },
+};
+
+use async_graphql::{Context, ErrorExtensions, Object, Result};
+
+use super::user::{User, UserRole};
+
+#[derive(Default)]
+pub struct AdminMutations;
+
+#[Object]
+impl AdminMutations {
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_create_user(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+        password: String,
+        roles: Vec<UserRole>,
+    ) -> Result<User> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let mailer = ctx.data::<Arc<dyn Mailer>>().unwrap();
+        let params = ctx.data::<Arc<hash_password::Params>>().unwrap();
+
+        let result = admin_create_user::execute(
+            repo.clone(),
+            tokens.clone(),
+            mailer.clone(),
+            params.clone(),
+            admin_create_user::Input {
+                email,
+                password,
+                roles: roles.into_iter().map(entities::Role::from).collect(),
+            },
+        )
+        .await;
+
+        match result {
+            Ok(user) => Ok(user.into()),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_set_roles(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        roles: Vec<UserRole>,
+    ) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+
+        let result = set_roles::execute(
+            repo.clone(),
+            id,
+            roles.into_iter().map(entities::Role::from).collect(),
+        )
+        .await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    /// Blocks sign in and ends every session the user has.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_disable_user(&self, ctx: &Context<'_>, id: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+
+        let result = disable_user::execute(repo.clone(), sessions.clone(), id).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_enable_user(&self, ctx: &Context<'_>, id: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+
+        let result = enable_user::execute(repo.clone(), id).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    /// Invalidates the user's password and sessions, then emails them a reset token.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_force_password_reset(&self, ctx: &Context<'_>, id: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let mailer = ctx.data::<Arc<dyn Mailer>>().unwrap();
+        let params = ctx.data::<Arc<hash_password::Params>>().unwrap();
+
+        let result = force_password_reset::execute(
+            repo.clone(),
+            sessions.clone(),
+            tokens.clone(),
+            mailer.clone(),
+            params.clone(),
+            id,
+        )
+        .await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_delete_user(&self, ctx: &Context<'_>, id: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+
+        let result = delete_user::execute(repo.clone(), sessions.clone(), id).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    /// Lifts a sign in lockout before it runs out.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn admin_unlock_user(&self, ctx: &Context<'_>, id: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let attempts = ctx.data::<Arc<dyn SignInAttemptRepository>>().unwrap();
+
+        let result = unlock_account::execute(repo.clone(), attempts.clone(), id).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+}
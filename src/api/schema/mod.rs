@@ -1,13 +1,237 @@
 use async_graphql::{EmptySubscription, MergedObject, Schema, SchemaBuilder};
 
+use super::{extensions::authentication::Authentication, Services};
+
+mod admin;
 mod user;
 
 #[derive(MergedObject, Default)]
 pub struct Query(user::UserQuery);
 
 #[derive(MergedObject, Default)]
-pub struct Mutation(user::UserMutations);
+pub struct Mutation(user::UserMutations, admin::AdminMutations);
+
+/// Builds the schema with `services` as the data its resolvers look up.
+pub fn build_schema(services: Services) -> SchemaBuilder<Query, Mutation, EmptySubscription> {
+    let Services {
+        repositories,
+        mailer,
+        keys,
+        password_hashing,
+        sign_in_settings,
+        clock,
+    } = services;
 
-pub fn build_schema() -> SchemaBuilder<Query, Mutation, EmptySubscription> {
     Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+        .data(repositories.users)
+        .data(repositories.sessions.clone())
+        .data(repositories.one_time_tokens)
+        .data(repositories.sign_in_attempts)
+        .data(mailer)
+        .data(keys)
+        .data(password_hashing)
+        .data(sign_in_settings)
+        .data(clock)
+        .extension(Authentication::new(repositories.sessions))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_graphql::Request;
+    use chrono::{Duration, Utc};
+
+    use crate::{
+        api::extensions::authentication::{self, AuthenticatedUser},
+        domain::{
+            clock::SystemClock,
+            user::{access_token, entities, entities::Role, hash_password, sign_in},
+        },
+        mailer::MockMailer,
+        repositories::{
+            error::{DatabaseError, DatabaseErrorKind},
+            one_time_token::MockRepository as MockOneTimeTokenRepository,
+            session::MockRepository as MockSessionRepository,
+            sign_in_attempt::MockRepository as MockSignInAttemptRepository,
+            user::{self, MockRepository},
+            Repositories,
+        },
+    };
+
+    use super::*;
+
+    const CALLER_ID: &str = "caller";
+
+    fn user(roles: Vec<Role>) -> entities::User {
+        entities::User::builder()
+            .id(CALLER_ID)
+            .email("someone@example.com")
+            .password("hash")
+            .roles(roles)
+            .build()
+    }
+
+    /// Sessions that vouch for the caller, as the authentication extension checks them first.
+    fn sessions() -> MockSessionRepository {
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_find_by_id().returning(|id| {
+            Ok(entities::Session {
+                id,
+                user_id: CALLER_ID.to_string(),
+                refresh_token_hash: "hash".to_string(),
+                revoked: false,
+                expires_at: Utc::now() + Duration::days(1),
+            })
+        });
+
+        sessions
+    }
+
+    fn keys() -> Arc<access_token::Keys> {
+        Arc::new(access_token::Keys::from_secret(
+            b"secret",
+            Duration::minutes(15),
+        ))
+    }
+
+    fn services(repositories: Repositories, mailer: MockMailer) -> Services {
+        Services {
+            repositories,
+            mailer: Arc::new(mailer),
+            keys: keys(),
+            password_hashing: Arc::new(hash_password::Params {
+                memory_kib: 1024,
+                iterations: 1,
+                parallelism: 1,
+            }),
+            sign_in_settings: Arc::new(sign_in::Settings {
+                response_padding: std::time::Duration::ZERO,
+                ..sign_in::Settings::default()
+            }),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    async fn execute(users: MockRepository, query: &str, signed_in: bool) -> serde_json::Value {
+        let repositories = Repositories {
+            users: Arc::new(users),
+            sessions: Arc::new(sessions()),
+            one_time_tokens: Arc::new(MockOneTimeTokenRepository::new()),
+            sign_in_attempts: Arc::new(MockSignInAttemptRepository::new()),
+        };
+        let schema = build_schema(services(repositories, MockMailer::new())).finish();
+
+        let mut request = Request::new(query);
+        if signed_in {
+            request = request.data(AuthenticatedUser {
+                id: CALLER_ID.to_string(),
+                session_id: "session".to_string(),
+            });
+        }
+
+        serde_json::to_value(schema.execute(request).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_return_user_to_themselves() {
+        let mut users = MockRepository::new();
+        users
+            .expect_find_by_id()
+            .withf(|id| id == CALLER_ID)
+            .returning(|_| Ok(user(vec![Role::User])));
+
+        let response = execute(users, r#"{ user(id: "caller") { email roles } }"#, true).await;
+
+        assert_eq!(response["data"]["user"]["email"], "someone@example.com");
+        assert_eq!(response["data"]["user"]["roles"][0], "USER");
+    }
+
+    #[tokio::test]
+    async fn should_return_internal_error_when_repository_fails() {
+        let mut users = MockRepository::new();
+        users.expect_find_by_id().returning(|_| {
+            Err(user::FindByIdError::Database(DatabaseError::new(
+                DatabaseErrorKind::Connectivity,
+                "unreachable",
+            )))
+        });
+
+        let response = execute(users, r#"{ user(id: "caller") { email } }"#, true).await;
+
+        assert_eq!(response["errors"][0]["extensions"]["code"], "INTERNAL");
+    }
+
+    #[tokio::test]
+    async fn should_forbid_users_connection_for_non_admins() {
+        let mut users = MockRepository::new();
+        users
+            .expect_find_by_id()
+            .returning(|_| Ok(user(vec![Role::User])));
+        users.expect_list().never();
+
+        let response = execute(users, "{ users { edges { node { email } } } }", true).await;
+
+        assert_eq!(response["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    }
+
+    #[tokio::test]
+    async fn should_return_conflict_when_registering_taken_email() {
+        let mut users = MockRepository::new();
+        users
+            .expect_find_one_by_email()
+            .returning(|_| Ok(Some(user(vec![Role::User]))));
+        users.expect_create().never();
+
+        let response = execute(
+            users,
+            r#"mutation { register(username: "Someone@Example.com", password: "correct horse battery") { id } }"#,
+            false,
+        )
+        .await;
+
+        assert_eq!(response["errors"][0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(response["errors"][0]["extensions"]["field"], "username");
+    }
+
+    #[tokio::test]
+    async fn should_register_sign_in_and_find_self_in_memory() {
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
+            .times(1)
+            .withf(|email| email.to == "someone@example.com")
+            .returning(|_| Ok(()));
+        let schema = build_schema(services(Repositories::in_memory(), mailer)).finish();
+
+        let registered = schema
+            .execute(
+                r#"mutation { register(username: "Someone@Example.com", password: "correct horse battery") { id } }"#,
+            )
+            .await;
+        assert!(registered.errors.is_empty(), "{:?}", registered.errors);
+
+        let signed_in = serde_json::to_value(
+            schema
+                .execute(
+                    r#"mutation {
+                        signIn(username: "someone@example.com", password: "correct horse battery") {
+                            ... on SignInPayload { accessToken }
+                        }
+                    }"#,
+                )
+                .await,
+        )
+        .unwrap();
+        let access_token = signed_in["data"]["signIn"]["accessToken"].as_str().unwrap();
+
+        let request = authentication::authenticate(
+            Request::new("{ me { email } }"),
+            &keys(),
+            Some(format!("Bearer {}", access_token)),
+        );
+        let me = serde_json::to_value(schema.execute(request).await).unwrap();
+
+        assert_eq!(me["data"]["me"]["email"], "someone@example.com");
+    }
 }
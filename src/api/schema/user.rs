@@ -1,16 +1,177 @@
 use std::sync::Arc;
 
 use crate::{
-    domain::user::{find_one, register, sign_in},
-    repositories::user::MongoRepository,
+    api::{
+        error::ApiError,
+        extensions::authentication::AuthenticatedUser,
+        guards::{RoleGuard, SelfGuard},
+    },
+    domain::{
+        clock::Clock,
+        user::{
+            access_token, change_password, confirm_totp, enroll_totp,
+            entities::{self, Role},
+            find_one, hash_password, list_users, refresh_session, register, request_password_reset,
+            resend_verification, reset_password, session, sign_in, sign_out, sign_out_everywhere,
+            verify_email, verify_totp,
+        },
+    },
+    mailer::Mailer,
+    repositories::{
+        one_time_token::Repository as OneTimeTokenRepository,
+        session::Repository as SessionRepository,
+        sign_in_attempt::Repository as SignInAttemptRepository,
+        user::{self as user_repository, Repository as UserRepository},
+    },
 };
 
-use async_graphql::{Context, Error, Object, Result, SimpleObject};
+use async_graphql::{
+    connection::{self, Connection, CursorType, Edge},
+    Context, Enum, ErrorExtensions, InputObject, Object, Result, SimpleObject, Union,
+};
+use chrono::{DateTime, TimeZone, Utc};
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "Role", remote = "entities::Role")]
+pub(super) enum UserRole {
+    User,
+    Admin,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "user_repository::ListOrder")]
+enum UserOrderBy {
+    CreatedAtAsc,
+    CreatedAtDesc,
+}
+
+#[derive(InputObject, Default)]
+struct UserFilter {
+    /// Case-insensitive substring of the email
+    email_contains: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    role: Option<UserRole>,
+}
+
+impl From<UserFilter> for user_repository::ListFilter {
+    fn from(filter: UserFilter) -> Self {
+        Self {
+            email_contains: filter.email_contains,
+            created_after: filter.created_after,
+            created_before: filter.created_before,
+            role: filter.role.map(Role::from),
+        }
+    }
+}
+
+/// Opaque to clients: the creation time in milliseconds and the id, base64 encoded.
+struct UserCursor(user_repository::Cursor);
+
+impl CursorType for UserCursor {
+    type Error = &'static str;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        let decoded = base64::decode_config(s, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or("Invalid Cursor")?;
+        let (millis, id) = decoded.split_once(':').ok_or("Invalid Cursor")?;
+        let millis = millis.parse::<i64>().map_err(|_| "Invalid Cursor")?;
+
+        Ok(Self(user_repository::Cursor {
+            created_at: Utc.timestamp_millis(millis),
+            id: id.to_string(),
+        }))
+    }
+
+    fn encode_cursor(&self) -> String {
+        base64::encode_config(
+            format!("{}:{}", self.0.created_at.timestamp_millis(), self.0.id),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+}
 
 #[derive(SimpleObject)]
-struct User {
+pub(super) struct User {
     id: String,
     email: String,
+    roles: Vec<UserRole>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<entities::User> for User {
+    fn from(user: entities::User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            roles: user.roles.into_iter().map(UserRole::from).collect(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct SignInPayload {
+    user: User,
+    access_token: String,
+    expires_at: DateTime<Utc>,
+    refresh_token: String,
+}
+
+impl From<sign_in::SignedIn> for SignInPayload {
+    fn from(signed_in: sign_in::SignedIn) -> Self {
+        Self {
+            user: signed_in.user.into(),
+            access_token: signed_in.tokens.access_token.token,
+            expires_at: signed_in.tokens.access_token.expires_at,
+            refresh_token: signed_in.tokens.refresh_token,
+        }
+    }
+}
+
+/// Returned by signIn instead of a session when the user has two-factor authentication on.
+#[derive(SimpleObject)]
+struct SecondFactorChallenge {
+    challenge: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Union)]
+enum SignInResult {
+    SignedIn(SignInPayload),
+    SecondFactorRequired(SecondFactorChallenge),
+}
+
+#[derive(SimpleObject)]
+struct TotpEnrollment {
+    secret: String,
+    uri: String,
+}
+
+#[derive(SimpleObject)]
+struct RefreshSessionPayload {
+    access_token: String,
+    expires_at: DateTime<Utc>,
+    refresh_token: String,
+}
+
+impl From<session::Tokens> for RefreshSessionPayload {
+    fn from(tokens: session::Tokens) -> Self {
+        Self {
+            access_token: tokens.access_token.token,
+            expires_at: tokens.access_token.expires_at,
+            refresh_token: tokens.refresh_token,
+        }
+    }
+}
+
+fn authenticated_user<'a>(ctx: &Context<'a>) -> Result<&'a AuthenticatedUser> {
+    ctx.data_opt::<AuthenticatedUser>()
+        .ok_or_else(|| ApiError::unauthenticated().extend())
 }
 
 #[derive(Default)]
@@ -21,19 +182,83 @@ pub struct UserMutations;
 
 #[Object]
 impl UserQuery {
+    #[graphql(guard = "SelfGuard::new(&id).or(RoleGuard::new(Role::Admin))")]
     async fn user(&self, ctx: &Context<'_>, id: String) -> Result<User> {
-        let repo = ctx.data::<Arc<MongoRepository>>().unwrap();
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
 
         let result = find_one::execute(repo.clone(), id).await;
 
         match result {
-            Ok(user) => Ok(User {
-                id: user.id,
-                email: user.email,
-            }),
-            Err(find_one::FindOneError::NotFound) => Err(Error::new("Not Found")),
-            Err(find_one::FindOneError::InvalidId) => Err(Error::new("Invalid Input")),
-            Err(find_one::FindOneError::Unknown) => Err(Error::new("Unknown")),
+            Ok(user) => Ok(user.into()),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+        filter: Option<UserFilter>,
+        order_by: Option<UserOrderBy>,
+    ) -> Result<Connection<UserCursor, User>> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<UserCursor>, before: Option<UserCursor>, first, last| async move {
+                let result = list_users::execute(
+                    repo.clone(),
+                    list_users::Input {
+                        filter: filter.unwrap_or_default().into(),
+                        order: order_by.unwrap_or(UserOrderBy::CreatedAtAsc).into(),
+                        after: after.map(|cursor| cursor.0),
+                        before: before.map(|cursor| cursor.0),
+                        first,
+                        last,
+                    },
+                )
+                .await;
+
+                let page = match result {
+                    Ok(page) => page,
+                    Err(error) => return Err(ApiError::from(error).extend()),
+                };
+
+                let mut connection = Connection::new(page.has_previous_page, page.has_next_page);
+                connection.append(
+                    page.users
+                        .into_iter()
+                        .map(|(cursor, user)| Edge::new(UserCursor(cursor), user.into())),
+                );
+
+                Ok(connection)
+            },
+        )
+        .await
+    }
+
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        let caller = match ctx.data_opt::<AuthenticatedUser>() {
+            Some(caller) => caller,
+            None => return Ok(None),
+        };
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+
+        let result = find_one::execute(repo.clone(), caller.id.clone()).await;
+
+        match result {
+            Ok(user) => Ok(Some(user.into())),
+            Err(find_one::FindOneError::NotFound) => Ok(None),
+            Err(find_one::FindOneError::InvalidId) => Ok(None),
+            Err(error) => Err(ApiError::from(error).extend()),
         }
     }
 }
@@ -46,10 +271,16 @@ impl UserMutations {
         username: String,
         password: String,
     ) -> Result<User> {
-        let repo = ctx.data::<Arc<MongoRepository>>().unwrap();
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let mailer = ctx.data::<Arc<dyn Mailer>>().unwrap();
+        let params = ctx.data::<Arc<hash_password::Params>>().unwrap();
 
         let result = register::execute(
             repo.clone(),
+            tokens.clone(),
+            mailer.clone(),
+            params.clone(),
             register::Input {
                 email: username,
                 password,
@@ -58,21 +289,33 @@ impl UserMutations {
         .await;
 
         match result {
-            Ok(user) => Ok(User {
-                id: user.id,
-                email: user.email,
-            }),
-            Err(register::RegisterError::AlreadyExists) => Err(Error::new("Already Exists")),
-            Err(register::RegisterError::Unknown) => Err(Error::new("Unknown Error")),
-            Err(register::RegisterError::InvalidPassword) => Err(Error::new("Invalid Password")),
+            Ok(user) => Ok(user.into()),
+            Err(error) => Err(ApiError::from(error).extend()),
         }
     }
 
-    async fn sign_in(&self, ctx: &Context<'_>, username: String, password: String) -> Result<User> {
-        let repo = ctx.data::<Arc<MongoRepository>>().unwrap();
+    async fn sign_in(
+        &self,
+        ctx: &Context<'_>,
+        username: String,
+        password: String,
+    ) -> Result<SignInResult> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let attempts = ctx.data::<Arc<dyn SignInAttemptRepository>>().unwrap();
+        let keys = ctx.data::<Arc<access_token::Keys>>().unwrap();
+        let params = ctx.data::<Arc<hash_password::Params>>().unwrap();
+        let settings = ctx.data::<Arc<sign_in::Settings>>().unwrap();
 
         let result = sign_in::execute(
             repo.clone(),
+            sessions.clone(),
+            tokens.clone(),
+            attempts.clone(),
+            keys.clone(),
+            params.clone(),
+            settings.clone(),
             sign_in::Input {
                 email: username,
                 password,
@@ -81,15 +324,229 @@ impl UserMutations {
         .await;
 
         match result {
-            Ok(user) => Ok(User {
-                id: user.id,
-                email: user.email,
-            }),
-            Err(sign_in::SignInError::Failed) => Err(Error::new("Login Failed")),
-            Err(sign_in::SignInError::Unknown) => Err(Error::new("Unknown Error")),
-            Err(sign_in::SignInError::InvalidPasswordFormat) => {
-                Err(Error::new("Invalid Password Format"))
+            Ok(sign_in::Output::SignedIn(signed_in)) => {
+                Ok(SignInResult::SignedIn((*signed_in).into()))
             }
+            Ok(sign_in::Output::SecondFactorRequired(challenge)) => {
+                Ok(SignInResult::SecondFactorRequired(SecondFactorChallenge {
+                    challenge: challenge.token,
+                    expires_at: challenge.expires_at,
+                }))
+            }
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    async fn verify_totp(
+        &self,
+        ctx: &Context<'_>,
+        challenge: String,
+        code: String,
+    ) -> Result<SignInPayload> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let attempts = ctx.data::<Arc<dyn SignInAttemptRepository>>().unwrap();
+        let keys = ctx.data::<Arc<access_token::Keys>>().unwrap();
+        let clock = ctx.data::<Arc<dyn Clock>>().unwrap();
+
+        let result = verify_totp::execute(
+            repo.clone(),
+            sessions.clone(),
+            tokens.clone(),
+            attempts.clone(),
+            keys.clone(),
+            clock.clone(),
+            verify_totp::Input { challenge, code },
+        )
+        .await;
+
+        match result {
+            Ok(signed_in) => Ok(signed_in.into()),
+            Err(error) => Err(ApiError::from(error).extend()),
         }
     }
+
+    async fn enroll_totp(&self, ctx: &Context<'_>) -> Result<TotpEnrollment> {
+        let caller = authenticated_user(ctx)?;
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+
+        let result = enroll_totp::execute(repo.clone(), caller.id.clone()).await;
+
+        match result {
+            Ok(enroll_totp::Enrollment { secret, uri }) => Ok(TotpEnrollment { secret, uri }),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    /// Returns the recovery codes, which can't be retrieved again afterwards.
+    async fn confirm_totp(&self, ctx: &Context<'_>, code: String) -> Result<Vec<String>> {
+        let caller = authenticated_user(ctx)?;
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let clock = ctx.data::<Arc<dyn Clock>>().unwrap();
+
+        let result = confirm_totp::execute(
+            repo.clone(),
+            clock.clone(),
+            confirm_totp::Input {
+                user_id: caller.id.clone(),
+                code,
+            },
+        )
+        .await;
+
+        match result {
+            Ok(recovery_codes) => Ok(recovery_codes),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    async fn refresh_session(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+    ) -> Result<RefreshSessionPayload> {
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+        let keys = ctx.data::<Arc<access_token::Keys>>().unwrap();
+
+        let result = refresh_session::execute(sessions.clone(), keys.clone(), token).await;
+
+        match result {
+            Ok(tokens) => Ok(tokens.into()),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    async fn sign_out(&self, ctx: &Context<'_>) -> Result<bool> {
+        let caller = authenticated_user(ctx)?;
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+
+        let result = sign_out::execute(sessions.clone(), caller.session_id.clone()).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    async fn sign_out_everywhere(&self, ctx: &Context<'_>) -> Result<bool> {
+        let caller = authenticated_user(ctx)?;
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+
+        let result = sign_out_everywhere::execute(sessions.clone(), caller.id.clone()).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    async fn change_password(
+        &self,
+        ctx: &Context<'_>,
+        current_password: String,
+        new_password: String,
+    ) -> Result<bool> {
+        let caller = authenticated_user(ctx)?;
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+        let params = ctx.data::<Arc<hash_password::Params>>().unwrap();
+
+        let result = change_password::execute(
+            repo.clone(),
+            sessions.clone(),
+            params.clone(),
+            change_password::Input {
+                user_id: caller.id.clone(),
+                session_id: caller.session_id.clone(),
+                current_password,
+                new_password,
+            },
+        )
+        .await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    /// Always succeeds so the response doesn't reveal whether the email is registered.
+    async fn request_password_reset(&self, ctx: &Context<'_>, email: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let mailer = ctx.data::<Arc<dyn Mailer>>().unwrap();
+        let settings = ctx.data::<Arc<sign_in::Settings>>().unwrap();
+
+        request_password_reset::execute(
+            repo.clone(),
+            tokens.clone(),
+            mailer.clone(),
+            settings.response_padding,
+            email,
+        )
+        .await;
+
+        Ok(true)
+    }
+
+    async fn reset_password(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        new_password: String,
+    ) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let sessions = ctx.data::<Arc<dyn SessionRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let params = ctx.data::<Arc<hash_password::Params>>().unwrap();
+
+        let result = reset_password::execute(
+            repo.clone(),
+            sessions.clone(),
+            tokens.clone(),
+            params.clone(),
+            reset_password::Input {
+                token,
+                new_password,
+            },
+        )
+        .await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    async fn verify_email(&self, ctx: &Context<'_>, token: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+
+        let result = verify_email::execute(repo.clone(), tokens.clone(), token).await;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => Err(ApiError::from(error).extend()),
+        }
+    }
+
+    /// Always succeeds so the response doesn't reveal whether the email is registered.
+    async fn resend_verification(&self, ctx: &Context<'_>, email: String) -> Result<bool> {
+        let repo = ctx.data::<Arc<dyn UserRepository>>().unwrap();
+        let tokens = ctx.data::<Arc<dyn OneTimeTokenRepository>>().unwrap();
+        let mailer = ctx.data::<Arc<dyn Mailer>>().unwrap();
+        let settings = ctx.data::<Arc<sign_in::Settings>>().unwrap();
+
+        resend_verification::execute(
+            repo.clone(),
+            tokens.clone(),
+            mailer.clone(),
+            settings.response_padding,
+            email,
+        )
+        .await;
+
+        Ok(true)
+    }
 }
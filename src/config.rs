@@ -0,0 +1,599 @@
+use std::{
+    env, fmt, fs, io,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use chrono::Duration;
+use serde::Deserialize;
+
+#[cfg(feature = "mongo")]
+use crate::repositories::{Collections, MongoSettings};
+use crate::{
+    api::rate_limit,
+    domain::user::{hash_password, lockout, sign_in},
+};
+
+/// Read when `CONFIG_FILE` isn't set, and skipped if it doesn't exist
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Where users, sessions and tokens are kept.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Storage {
+    #[cfg(feature = "mongo")]
+    Mongo(MongoSettings),
+    /// Kept in the server process and lost when it stops, for local development and tests
+    Memory,
+    /// A SQLite or PostgreSQL database, depending on the scheme of `url`
+    #[cfg(feature = "sql")]
+    Sql { url: String },
+}
+
+pub struct Config {
+    pub bind_address: SocketAddr,
+    pub storage: Storage,
+    pub jwt_secret: String,
+    pub access_token_ttl: Duration,
+    pub password_hashing: hash_password::Params,
+    /// Directory emails are written to instead of being delivered
+    pub mail_directory: PathBuf,
+    pub sign_in: sign_in::Settings,
+    pub rate_limit: rate_limit::Settings,
+}
+
+/// Settings are named by their environment variable, whether they came from it or the file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read or holds something other than the settings below
+    File(PathBuf, String),
+    Missing(&'static str),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File(path, message) => {
+                write!(f, "cannot load {}: {}", path.display(), message)
+            }
+            ConfigError::Missing(name) => write!(f, "{} must be set", name),
+            ConfigError::Invalid(name) => write!(f, "{} is invalid", name),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The config file as written, where every setting may be left out.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    server: ServerFile,
+    storage: StorageFile,
+    #[cfg_attr(not(feature = "mongo"), allow(dead_code))]
+    mongo: MongoFile,
+    access_token: AccessTokenFile,
+    password_hashing: PasswordHashingFile,
+    mail: MailFile,
+    sign_in: SignInFile,
+    rate_limit: RateLimitFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ServerFile {
+    bind_address: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct StorageFile {
+    backend: Option<String>,
+    #[cfg_attr(not(feature = "sql"), allow(dead_code))]
+    database_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+#[cfg_attr(not(feature = "mongo"), allow(dead_code))]
+struct MongoFile {
+    uri: Option<String>,
+    app_name: Option<String>,
+    collections: CollectionsFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+#[cfg_attr(not(feature = "mongo"), allow(dead_code))]
+struct CollectionsFile {
+    users: Option<String>,
+    sessions: Option<String>,
+    one_time_tokens: Option<String>,
+    sign_in_attempts: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct AccessTokenFile {
+    secret: Option<String>,
+    ttl_seconds: Option<i64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct PasswordHashingFile {
+    memory_kib: Option<u32>,
+    iterations: Option<u32>,
+    parallelism: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MailFile {
+    directory: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SignInFile {
+    require_verified_email: Option<bool>,
+    max_failed_attempts: Option<u32>,
+    lockout_seconds: Option<i64>,
+    failure_window_seconds: Option<i64>,
+    response_padding_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RateLimitFile {
+    per_minute: Option<u32>,
+    sensitive_per_minute: Option<u32>,
+    trusted_proxies: Option<Vec<IpAddr>>,
+}
+
+/// Looks up an environment variable.
+type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+impl Config {
+    /// Reads the file named by `CONFIG_FILE` (`config.toml` if there is one), letting
+    /// environment variables override anything it sets.
+    pub fn load() -> Result<Self, ConfigError> {
+        let (path, required) = match env::var_os("CONFIG_FILE") {
+            Some(path) => (PathBuf::from(path), true),
+            None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+
+        let file = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|err| ConfigError::File(path.clone(), err.to_string()))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => FileConfig::default(),
+            Err(err) => return Err(ConfigError::File(path, err.to_string())),
+        };
+
+        Self::from_sources(file, &|name| env::var(name).ok())
+    }
+
+    fn from_sources(file: FileConfig, env: Env) -> Result<Self, ConfigError> {
+        let bind_address = parse_or(
+            env,
+            "BIND_ADDRESS",
+            file.server.bind_address,
+            SocketAddr::from(([0, 0, 0, 0], 8000)),
+        )?;
+
+        let storage = match setting(env, "STORAGE", file.storage.backend)?.as_deref() {
+            #[cfg(feature = "mongo")]
+            Some("mongo") | None => Storage::Mongo(mongo_settings(env, file.mongo)?),
+            #[cfg(not(feature = "mongo"))]
+            None => return Err(ConfigError::Missing("STORAGE")),
+            Some("memory") => Storage::Memory,
+            #[cfg(feature = "sql")]
+            Some("sql") => Storage::Sql {
+                url: setting(env, "DATABASE_URL", file.storage.database_url)?
+                    .ok_or(ConfigError::Missing("DATABASE_URL"))?,
+            },
+            Some(_) => return Err(ConfigError::Invalid("STORAGE")),
+        };
+
+        let jwt_secret = setting(env, "JWT_SECRET", file.access_token.secret)?
+            .ok_or(ConfigError::Missing("JWT_SECRET"))?;
+
+        if jwt_secret.is_empty() {
+            return Err(ConfigError::Invalid("JWT_SECRET"));
+        }
+
+        let access_token_ttl = parse_or(
+            env,
+            "ACCESS_TOKEN_TTL_SECONDS",
+            file.access_token.ttl_seconds,
+            15 * 60,
+        )?;
+
+        if access_token_ttl <= 0 {
+            return Err(ConfigError::Invalid("ACCESS_TOKEN_TTL_SECONDS"));
+        }
+
+        let defaults = hash_password::Params::default();
+        let password_hashing = hash_password::Params {
+            memory_kib: parse_or(
+                env,
+                "ARGON2_MEMORY_KIB",
+                file.password_hashing.memory_kib,
+                defaults.memory_kib,
+            )?,
+            iterations: parse_or(
+                env,
+                "ARGON2_ITERATIONS",
+                file.password_hashing.iterations,
+                defaults.iterations,
+            )?,
+            parallelism: parse_or(
+                env,
+                "ARGON2_PARALLELISM",
+                file.password_hashing.parallelism,
+                defaults.parallelism,
+            )?,
+        };
+
+        if password_hashing.iterations == 0 {
+            return Err(ConfigError::Invalid("ARGON2_ITERATIONS"));
+        }
+        if password_hashing.parallelism == 0 {
+            return Err(ConfigError::Invalid("ARGON2_PARALLELISM"));
+        }
+        if password_hashing.memory_kib < 8 * password_hashing.parallelism {
+            return Err(ConfigError::Invalid("ARGON2_MEMORY_KIB"));
+        }
+
+        let mail_directory = parse_or(
+            env,
+            "MAIL_DIRECTORY",
+            file.mail.directory,
+            PathBuf::from("outbox"),
+        )?;
+
+        let sign_in_defaults = sign_in::Settings::default();
+        let max_failed_attempts = parse_or(
+            env,
+            "SIGN_IN_MAX_FAILED_ATTEMPTS",
+            file.sign_in.max_failed_attempts,
+            sign_in_defaults.lockout.max_failed_attempts,
+        )?;
+        let lockout_seconds = parse_or(
+            env,
+            "SIGN_IN_LOCKOUT_SECONDS",
+            file.sign_in.lockout_seconds,
+            sign_in_defaults.lockout.duration.num_seconds(),
+        )?;
+        let failure_window_seconds = parse_or(
+            env,
+            "SIGN_IN_FAILURE_WINDOW_SECONDS",
+            file.sign_in.failure_window_seconds,
+            sign_in_defaults.lockout.failure_window.num_seconds(),
+        )?;
+        let response_padding_ms = parse_or(
+            env,
+            "SIGN_IN_RESPONSE_PADDING_MS",
+            file.sign_in.response_padding_ms,
+            sign_in_defaults.response_padding.as_millis() as u64,
+        )?;
+
+        if max_failed_attempts == 0 {
+            return Err(ConfigError::Invalid("SIGN_IN_MAX_FAILED_ATTEMPTS"));
+        }
+        if lockout_seconds <= 0 {
+            return Err(ConfigError::Invalid("SIGN_IN_LOCKOUT_SECONDS"));
+        }
+        if failure_window_seconds <= 0 {
+            return Err(ConfigError::Invalid("SIGN_IN_FAILURE_WINDOW_SECONDS"));
+        }
+        // Without padding, response times give away which emails have accounts
+        if response_padding_ms == 0 {
+            return Err(ConfigError::Invalid("SIGN_IN_RESPONSE_PADDING_MS"));
+        }
+
+        let sign_in = sign_in::Settings {
+            require_verified_email: parse_or(
+                env,
+                "REQUIRE_VERIFIED_EMAIL",
+                file.sign_in.require_verified_email,
+                sign_in_defaults.require_verified_email,
+            )?,
+            lockout: lockout::Settings {
+                max_failed_attempts,
+                duration: Duration::seconds(lockout_seconds),
+                failure_window: Duration::seconds(failure_window_seconds),
+            },
+            response_padding: std::time::Duration::from_millis(response_padding_ms),
+        };
+
+        let rate_limit_defaults = rate_limit::Settings::default();
+        let requests_per_minute = parse_or(
+            env,
+            "RATE_LIMIT_PER_MINUTE",
+            file.rate_limit.per_minute,
+            rate_limit_defaults.requests.capacity,
+        )?;
+        let sensitive_per_minute = parse_or(
+            env,
+            "SENSITIVE_RATE_LIMIT_PER_MINUTE",
+            file.rate_limit.sensitive_per_minute,
+            rate_limit_defaults.sensitive_operations.capacity,
+        )?;
+
+        if requests_per_minute == 0 {
+            return Err(ConfigError::Invalid("RATE_LIMIT_PER_MINUTE"));
+        }
+        if sensitive_per_minute == 0 {
+            return Err(ConfigError::Invalid("SENSITIVE_RATE_LIMIT_PER_MINUTE"));
+        }
+
+        let trusted_proxies = match env("TRUSTED_PROXIES") {
+            Some(value) => value
+                .split(',')
+                .filter(|proxy| !proxy.trim().is_empty())
+                .map(|proxy| proxy.trim().parse::<IpAddr>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| ConfigError::Invalid("TRUSTED_PROXIES"))?,
+            None => file
+                .rate_limit
+                .trusted_proxies
+                .unwrap_or(rate_limit_defaults.trusted_proxies),
+        };
+
+        let rate_limit = rate_limit::Settings {
+            requests: rate_limit::Quota::per_minute(requests_per_minute),
+            sensitive_operations: rate_limit::Quota::per_minute(sensitive_per_minute),
+            trusted_proxies,
+        };
+
+        Ok(Self {
+            bind_address,
+            storage,
+            jwt_secret,
+            access_token_ttl: Duration::seconds(access_token_ttl),
+            password_hashing,
+            mail_directory,
+            sign_in,
+            rate_limit,
+        })
+    }
+}
+
+#[cfg(feature = "mongo")]
+fn mongo_settings(env: Env, file: MongoFile) -> Result<MongoSettings, ConfigError> {
+    let defaults = MongoSettings::default();
+    let settings = MongoSettings {
+        uri: parse_or(env, "MONGO_URI", file.uri, defaults.uri)?,
+        app_name: parse_or(env, "MONGO_APP_NAME", file.app_name, defaults.app_name)?,
+        collections: Collections {
+            users: parse_or(
+                env,
+                "MONGO_USERS_COLLECTION",
+                file.collections.users,
+                defaults.collections.users,
+            )?,
+            sessions: parse_or(
+                env,
+                "MONGO_SESSIONS_COLLECTION",
+                file.collections.sessions,
+                defaults.collections.sessions,
+            )?,
+            one_time_tokens: parse_or(
+                env,
+                "MONGO_ONE_TIME_TOKENS_COLLECTION",
+                file.collections.one_time_tokens,
+                defaults.collections.one_time_tokens,
+            )?,
+            sign_in_attempts: parse_or(
+                env,
+                "MONGO_SIGN_IN_ATTEMPTS_COLLECTION",
+                file.collections.sign_in_attempts,
+                defaults.collections.sign_in_attempts,
+            )?,
+        },
+    };
+
+    if !settings.uri.starts_with("mongodb://") && !settings.uri.starts_with("mongodb+srv://") {
+        return Err(ConfigError::Invalid("MONGO_URI"));
+    }
+
+    for (name, value) in [
+        ("MONGO_APP_NAME", &settings.app_name),
+        ("MONGO_USERS_COLLECTION", &settings.collections.users),
+        ("MONGO_SESSIONS_COLLECTION", &settings.collections.sessions),
+        (
+            "MONGO_ONE_TIME_TOKENS_COLLECTION",
+            &settings.collections.one_time_tokens,
+        ),
+        (
+            "MONGO_SIGN_IN_ATTEMPTS_COLLECTION",
+            &settings.collections.sign_in_attempts,
+        ),
+    ] {
+        if value.trim().is_empty() {
+            return Err(ConfigError::Invalid(name));
+        }
+    }
+
+    Ok(settings)
+}
+
+/// The environment variable `name` if it is set, otherwise what the file says.
+fn setting<T: FromStr>(
+    env: Env,
+    name: &'static str,
+    file: Option<T>,
+) -> Result<Option<T>, ConfigError> {
+    match env(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| ConfigError::Invalid(name)),
+        None => Ok(file),
+    }
+}
+
+fn parse_or<T: FromStr>(
+    env: Env,
+    name: &'static str,
+    file: Option<T>,
+    default: T,
+) -> Result<T, ConfigError> {
+    Ok(setting(env, name, file)?.unwrap_or(default))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn load(file: &str, vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Config::from_sources(toml::from_str(file).unwrap(), &|name| {
+            vars.get(name).cloned()
+        })
+    }
+
+    #[test]
+    fn should_read_settings_from_file() {
+        let config = load(
+            r#"
+            [server]
+            bind_address = "127.0.0.1:9000"
+
+            [storage]
+            backend = "memory"
+
+            [access_token]
+            secret = "from-file"
+            ttl_seconds = 60
+
+            [sign_in]
+            response_padding_ms = 250
+
+            [rate_limit]
+            trusted_proxies = ["10.0.0.1"]
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.bind_address,
+            SocketAddr::from(([127, 0, 0, 1], 9000))
+        );
+        assert_eq!(config.storage, Storage::Memory);
+        assert_eq!(config.jwt_secret, "from-file");
+        assert_eq!(config.access_token_ttl, Duration::seconds(60));
+        assert_eq!(
+            config.sign_in.response_padding,
+            std::time::Duration::from_millis(250)
+        );
+        assert_eq!(
+            config.rate_limit.trusted_proxies,
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn should_let_environment_override_file() {
+        let config = load(
+            r#"
+            [server]
+            bind_address = "127.0.0.1:9000"
+
+            [storage]
+            backend = "memory"
+
+            [access_token]
+            secret = "from-file"
+            "#,
+            &[
+                ("BIND_ADDRESS", "0.0.0.0:8080"),
+                ("JWT_SECRET", "from-environment"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.bind_address, SocketAddr::from(([0, 0, 0, 0], 8080)));
+        assert_eq!(config.jwt_secret, "from-environment");
+    }
+
+    #[test]
+    fn should_default_everything_but_the_secret() {
+        let config = load("", &[("STORAGE", "memory"), ("JWT_SECRET", "secret")]).unwrap();
+
+        assert_eq!(config.bind_address, SocketAddr::from(([0, 0, 0, 0], 8000)));
+        assert_eq!(config.sign_in.response_padding, sign_in::RESPONSE_PADDING);
+        assert!(matches!(
+            load("", &[("STORAGE", "memory")]),
+            Err(ConfigError::Missing("JWT_SECRET"))
+        ));
+    }
+
+    #[test]
+    fn should_name_invalid_setting_whichever_source_it_came_from() {
+        let from_file = load(
+            "[password_hashing]\niterations = 0",
+            &[("STORAGE", "memory"), ("JWT_SECRET", "secret")],
+        );
+        let from_environment = load(
+            "",
+            &[
+                ("STORAGE", "memory"),
+                ("JWT_SECRET", "secret"),
+                ("BIND_ADDRESS", "localhost"),
+            ],
+        );
+
+        assert!(matches!(
+            from_file,
+            Err(ConfigError::Invalid("ARGON2_ITERATIONS"))
+        ));
+        assert!(matches!(
+            from_environment,
+            Err(ConfigError::Invalid("BIND_ADDRESS"))
+        ));
+    }
+
+    #[test]
+    fn should_reject_unknown_keys_in_file() {
+        let file = toml::from_str::<FileConfig>("[server]\nport = 8000");
+
+        assert!(file.is_err());
+    }
+
+    #[cfg(feature = "mongo")]
+    #[test]
+    fn should_configure_mongo_collections() {
+        let config = load(
+            r#"
+            [mongo]
+            uri = "mongodb://db:27017/auth"
+
+            [mongo.collections]
+            users = "accounts"
+            "#,
+            &[
+                ("JWT_SECRET", "secret"),
+                ("MONGO_SESSIONS_COLLECTION", "logins"),
+            ],
+        )
+        .unwrap();
+
+        let Storage::Mongo(mongo) = config.storage else {
+            panic!("expected Mongo storage");
+        };
+        assert_eq!(mongo.uri, "mongodb://db:27017/auth");
+        assert_eq!(mongo.collections.users, "accounts");
+        assert_eq!(mongo.collections.sessions, "logins");
+        assert_eq!(mongo.collections.one_time_tokens, "one_time_tokens");
+    }
+}
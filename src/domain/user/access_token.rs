@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Claims {
+    pub sub: String,
+    /// Id of the session the token was issued for, so it stops working once that is revoked
+    pub sid: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+pub struct Keys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    ttl: Duration,
+}
+
+impl Keys {
+    pub fn from_secret(secret: &[u8], ttl: Duration) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            ttl,
+        }
+    }
+}
+
+pub struct AccessToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum VerifyError {
+    Expired,
+    Invalid,
+}
+
+pub fn issue(keys: &Keys, user_id: &str, session_id: &str) -> Result<AccessToken, ()> {
+    let issued_at = Utc::now();
+    let expires_at = issued_at + keys.ttl;
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        sid: session_id.to_string(),
+        iat: issued_at.timestamp(),
+        exp: expires_at.timestamp(),
+    };
+
+    let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &keys.encoding)
+        .map_err(|_| ())?;
+
+    Ok(AccessToken {
+        token,
+        expires_at: Utc.timestamp(claims.exp, 0),
+    })
+}
+
+pub fn verify(keys: &Keys, token: &str) -> Result<Claims, VerifyError> {
+    let result =
+        jsonwebtoken::decode::<Claims>(token, &keys.decoding, &Validation::new(Algorithm::HS256));
+
+    match result {
+        Ok(data) => Ok(data.claims),
+        Err(err) => match err.kind() {
+            ErrorKind::ExpiredSignature => Err(VerifyError::Expired),
+            _ => Err(VerifyError::Invalid),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_sign_claims_for_user() {
+        let keys = Keys::from_secret(b"secret", Duration::minutes(15));
+
+        let access_token = issue(&keys, "id", "session").unwrap();
+
+        let claims = verify(&keys, &access_token.token).unwrap();
+
+        assert_eq!(claims.sub, "id");
+        assert_eq!(claims.sid, "session");
+        assert_eq!(claims.exp - claims.iat, 15 * 60);
+        assert_eq!(claims.exp, access_token.expires_at.timestamp());
+    }
+
+    #[test]
+    fn should_not_verify_with_another_secret() {
+        let keys = Keys::from_secret(b"secret", Duration::minutes(15));
+        let other_keys = Keys::from_secret(b"other", Duration::minutes(15));
+
+        let access_token = issue(&keys, "id", "session").unwrap();
+
+        assert_eq!(
+            verify(&other_keys, &access_token.token).unwrap_err(),
+            VerifyError::Invalid
+        );
+    }
+
+    #[test]
+    fn should_not_verify_expired_token() {
+        let keys = Keys::from_secret(b"secret", Duration::minutes(-5));
+
+        let access_token = issue(&keys, "id", "session").unwrap();
+
+        assert_eq!(
+            verify(&keys, &access_token.token).unwrap_err(),
+            VerifyError::Expired
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use crate::{
+    mailer::Mailer,
+    repositories::{one_time_token, user},
+};
+
+use super::{
+    email_address, email_verification,
+    entities::{Role, User},
+    hash_password, password_policy,
+};
+
+pub struct Input {
+    pub email: String,
+    pub password: String,
+    pub roles: Vec<Role>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum AdminCreateUserError {
+    InvalidPassword,
+    AlreadyExists,
+    Unknown,
+}
+
+/// Creates an account on someone's behalf, which otherwise goes through the same checks and
+/// verification email as `register`.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    params: Arc<hash_password::Params>,
+    input: Input,
+) -> Result<User, AdminCreateUserError> {
+    let Input {
+        email,
+        password,
+        roles,
+    } = input;
+
+    let email = email_address::normalize(&email);
+
+    if password_policy::validate(&password).is_err() {
+        return Err(AdminCreateUserError::InvalidPassword);
+    }
+
+    let previous_user = repo.find_one_by_email(email.clone()).await;
+
+    match previous_user {
+        Ok(None) => {}
+        Ok(Some(_)) => return Err(AdminCreateUserError::AlreadyExists),
+        Err(err @ user::FindOneByEmailError::Database(_)) => {
+            log::error!("Error In admin_create_user: {}", err);
+            return Err(AdminCreateUserError::Unknown);
+        }
+    };
+
+    let hashed_password = match hash_password::spawn_execute(password, *params).await {
+        Ok(hash) => hash,
+        Err(_) => return Err(AdminCreateUserError::InvalidPassword),
+    };
+
+    let results = repo
+        .create(user::CreateInput {
+            email,
+            password: hashed_password,
+            roles: unique(roles),
+        })
+        .await;
+
+    let user = match results {
+        Ok(user) => user,
+        // Someone took the email between the check above and now
+        Err(user::CreateError::AlreadyExists) => return Err(AdminCreateUserError::AlreadyExists),
+        Err(err @ user::CreateError::Database(_)) => {
+            log::error!("Error In admin_create_user: {}", err);
+            return Err(AdminCreateUserError::Unknown);
+        }
+    };
+
+    if email_verification::send(tokens.as_ref(), mailer.as_ref(), &user)
+        .await
+        .is_err()
+    {
+        log::error!("Error sending verification email to user {}", user.id);
+    }
+
+    Ok(user)
+}
+
+/// Drops repeated roles, falling back to a plain user when none are given.
+pub fn unique(roles: Vec<Role>) -> Vec<Role> {
+    let mut unique = Vec::new();
+    for role in roles {
+        if !unique.contains(&role) {
+            unique.push(role);
+        }
+    }
+
+    if unique.is_empty() {
+        unique.push(Role::User);
+    }
+
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        domain::user::entities::OneTimeToken,
+        mailer::MockMailer,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    fn params() -> Arc<hash_password::Params> {
+        Arc::new(hash_password::Params {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn should_create_user_with_given_roles() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(|_| Ok(None));
+        repo.expect_create()
+            .times(1)
+            .withf(|input| input.roles == vec![Role::Admin])
+            .returning(|input| {
+                Ok(User::builder()
+                    .email(input.email)
+                    .password(input.password)
+                    .roles(input.roles)
+                    .build())
+            });
+
+        let mut tokens = MockTokenRepository::new();
+        tokens.expect_create().times(1).returning(|input| {
+            Ok(OneTimeToken {
+                id: "token".to_string(),
+                user_id: input.user_id,
+                purpose: input.purpose,
+                created_at: Utc::now(),
+                expires_at: input.expires_at,
+            })
+        });
+
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(1).returning(|_| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(tokens),
+            Arc::new(mailer),
+            params(),
+            Input {
+                email: "email".to_string(),
+                password: "password".to_string(),
+                roles: vec![Role::Admin, Role::Admin],
+            },
+        )
+        .await;
+
+        match results {
+            Ok(user) => assert_eq!(user.roles, vec![Role::Admin]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_already_exists_error_when_already_found() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(|email| Ok(Some(User::builder().email(email).password("hash").build())));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            params(),
+            Input {
+                email: "email".to_string(),
+                password: "password".to_string(),
+                roles: Vec::new(),
+            },
+        )
+        .await;
+
+        assert_eq!(results.err(), Some(AdminCreateUserError::AlreadyExists));
+    }
+
+    #[test]
+    fn should_default_to_user_role() {
+        assert_eq!(unique(Vec::new()), vec![Role::User]);
+    }
+}
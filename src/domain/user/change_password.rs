@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use crate::repositories::{session, user};
+
+use super::{hash_password, password_policy};
+
+pub struct Input {
+    pub user_id: String,
+    pub session_id: String,
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ChangePasswordError {
+    NotFound,
+    IncorrectPassword,
+    InvalidPassword,
+    Unknown,
+}
+
+/// Replaces the user's password and signs out every other session they have open.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session::Repository>,
+    params: Arc<hash_password::Params>,
+    input: Input,
+) -> Result<(), ChangePasswordError> {
+    let Input {
+        user_id,
+        session_id,
+        current_password,
+        new_password,
+    } = input;
+
+    let user = match repo.find_by_id(user_id.clone()).await {
+        Ok(user) => user,
+        Err(user::FindByIdError::NotFound) | Err(user::FindByIdError::InvalidId) => {
+            return Err(ChangePasswordError::NotFound)
+        }
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In change_password: {}", err);
+            return Err(ChangePasswordError::Unknown);
+        }
+    };
+
+    match hash_password::spawn_verify(current_password, user.password).await {
+        Ok(true) => {}
+        Ok(false) | Err(_) => return Err(ChangePasswordError::IncorrectPassword),
+    };
+
+    if password_policy::validate(&new_password).is_err() {
+        return Err(ChangePasswordError::InvalidPassword);
+    }
+
+    let hashed_password = match hash_password::spawn_execute(new_password, *params).await {
+        Ok(hash) => hash,
+        Err(_) => return Err(ChangePasswordError::InvalidPassword),
+    };
+
+    match repo.update_password(user_id.clone(), hashed_password).await {
+        Ok(()) => {}
+        Err(user::UpdatePasswordError::NotFound) | Err(user::UpdatePasswordError::InvalidId) => {
+            return Err(ChangePasswordError::NotFound)
+        }
+        Err(err @ user::UpdatePasswordError::Database(_)) => {
+            log::error!("Error In change_password: {}", err);
+            return Err(ChangePasswordError::Unknown);
+        }
+    };
+
+    match sessions
+        .revoke_all_for_user(user_id, Some(session_id))
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(_) => Err(ChangePasswordError::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        domain::user::entities::User,
+        repositories::{session::MockRepository as MockSessionRepository, user::MockRepository},
+    };
+
+    use super::*;
+
+    const PARAMS: hash_password::Params = hash_password::Params {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn repo_with_password(password: &'static str) -> MockRepository {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id().times(1).returning(move |id| {
+            Ok(User::builder()
+                .id(id)
+                .password(hash_password::execute(password.to_string(), &PARAMS).unwrap())
+                .build())
+        });
+
+        repo
+    }
+
+    fn input(current_password: &str, new_password: &str) -> Input {
+        Input {
+            user_id: "id".to_string(),
+            session_id: "session".to_string(),
+            current_password: current_password.to_string(),
+            new_password: new_password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_update_password_and_revoke_other_sessions() {
+        let mut repo = repo_with_password("password");
+        repo.expect_update_password()
+            .times(1)
+            .withf(|id, password| {
+                id == "id" && hash_password::verify("new password", password) == Ok(true)
+            })
+            .returning(|_, _| Ok(()));
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all_for_user()
+            .times(1)
+            .withf(|user_id, except_id| user_id == "id" && except_id.as_deref() == Some("session"))
+            .returning(|_, _| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(sessions),
+            Arc::new(PARAMS),
+            input("password", "new password"),
+        )
+        .await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_incorrect_password_error_when_current_password_doesnt_match() {
+        let repo = repo_with_password("password");
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(PARAMS),
+            input("wrong password", "new password"),
+        )
+        .await;
+
+        assert_eq!(results, Err(ChangePasswordError::IncorrectPassword));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_password_error_when_policy_fails() {
+        let repo = repo_with_password("password");
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(PARAMS),
+            input("password", "short"),
+        )
+        .await;
+
+        assert_eq!(results, Err(ChangePasswordError::InvalidPassword));
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_when_user_is_missing() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id()
+            .times(1)
+            .returning(|_| Err(user::FindByIdError::NotFound));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(PARAMS),
+            input("password", "new password"),
+        )
+        .await;
+
+        assert_eq!(results, Err(ChangePasswordError::NotFound));
+    }
+}
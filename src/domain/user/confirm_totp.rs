@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use crate::{domain::clock::Clock, repositories::user};
+
+use super::{entities::TwoFactor, recovery_codes, totp};
+
+pub struct Input {
+    pub user_id: String,
+    pub code: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ConfirmTotpError {
+    NotFound,
+    NotEnrolled,
+    InvalidCode,
+    Unknown,
+}
+
+/// Turns on two-factor authentication once the user proves their app generates codes for the
+/// pending secret, returning recovery codes that are only ever shown this once.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    clock: Arc<dyn Clock>,
+    input: Input,
+) -> Result<Vec<String>, ConfirmTotpError> {
+    let Input { user_id, code } = input;
+
+    let user = match repo.find_by_id(user_id).await {
+        Ok(user) => user,
+        Err(user::FindByIdError::NotFound) | Err(user::FindByIdError::InvalidId) => {
+            return Err(ConfirmTotpError::NotFound)
+        }
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In confirm_totp: {}", err);
+            return Err(ConfirmTotpError::Unknown);
+        }
+    };
+
+    let secret = match user.two_factor.pending_secret {
+        Some(secret) => secret,
+        None => return Err(ConfirmTotpError::NotEnrolled),
+    };
+
+    let step = match totp::verify(&secret, &code, clock.now().timestamp()) {
+        Some(step) => step,
+        None => return Err(ConfirmTotpError::InvalidCode),
+    };
+
+    let (codes, code_hashes) = recovery_codes::generate().into_iter().unzip();
+
+    let results = repo
+        .set_two_factor(
+            user.id,
+            TwoFactor {
+                pending_secret: None,
+                secret: Some(secret),
+                recovery_code_hashes: code_hashes,
+                // The code that confirmed enrollment can't be replayed to sign in
+                last_totp_step: Some(step),
+            },
+        )
+        .await;
+
+    match results {
+        Ok(()) => Ok(codes),
+        Err(user::SetTwoFactorError::NotFound) | Err(user::SetTwoFactorError::InvalidId) => {
+            Err(ConfirmTotpError::NotFound)
+        }
+        Err(err @ user::SetTwoFactorError::Database(_)) => {
+            log::error!("Error In confirm_totp: {}", err);
+            Err(ConfirmTotpError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::{
+        domain::{clock::FixedClock, user::entities::User},
+        repositories::user::MockRepository,
+    };
+
+    use super::*;
+
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const NOW: i64 = 1111111109;
+
+    fn clock() -> Arc<FixedClock> {
+        Arc::new(FixedClock(Utc.timestamp(NOW, 0)))
+    }
+
+    fn repo_with_pending_secret() -> MockRepository {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id().times(1).returning(|id| {
+            Ok(User::builder()
+                .id(id)
+                .password("hash")
+                .two_factor(TwoFactor {
+                    pending_secret: Some(SECRET.to_string()),
+                    ..TwoFactor::default()
+                })
+                .build())
+        });
+
+        repo
+    }
+
+    #[tokio::test]
+    async fn should_enable_two_factor_with_hashed_recovery_codes() {
+        let mut repo = repo_with_pending_secret();
+        repo.expect_set_two_factor()
+            .times(1)
+            .withf(|id, two_factor| {
+                id == "id"
+                    && two_factor.pending_secret.is_none()
+                    && two_factor.secret.as_deref() == Some(SECRET)
+                    && two_factor.recovery_code_hashes.len() == 10
+                    && two_factor.last_totp_step == Some(NOW / 30)
+            })
+            .returning(|_, _| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            clock(),
+            Input {
+                user_id: "id".to_string(),
+                code: totp::code_at(SECRET, NOW).unwrap(),
+            },
+        )
+        .await;
+
+        match results {
+            Ok(codes) => assert_eq!(codes.len(), 10),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_code_error_for_wrong_code() {
+        let repo = repo_with_pending_secret();
+
+        let results = execute(
+            Arc::new(repo),
+            clock(),
+            Input {
+                user_id: "id".to_string(),
+                code: totp::code_at(SECRET, NOW - 300).unwrap(),
+            },
+        )
+        .await;
+
+        assert_eq!(results, Err(ConfirmTotpError::InvalidCode));
+    }
+}
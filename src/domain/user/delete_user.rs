@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::repositories::{session, user};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum DeleteUserError {
+    NotFound,
+    Unknown,
+}
+
+/// Removes the account for good, along with every session it still has.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session::Repository>,
+    user_id: String,
+) -> Result<(), DeleteUserError> {
+    let results = repo.delete(user_id.clone()).await;
+
+    match results {
+        Ok(()) => {}
+        Err(user::DeleteError::NotFound) | Err(user::DeleteError::InvalidId) => {
+            return Err(DeleteUserError::NotFound)
+        }
+        Err(err @ user::DeleteError::Database(_)) => {
+            log::error!("Error In delete_user: {}", err);
+            return Err(DeleteUserError::Unknown);
+        }
+    };
+
+    match sessions.revoke_all_for_user(user_id, None).await {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            log::error!("Error In delete_user: {}", err);
+            Err(DeleteUserError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::{
+        session::MockRepository as MockSessionRepository, user::MockRepository,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_delete_user_and_revoke_sessions() {
+        let mut repo = MockRepository::new();
+        repo.expect_delete()
+            .times(1)
+            .withf(|id| id == "id")
+            .returning(|_| Ok(()));
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all_for_user()
+            .times(1)
+            .withf(|user_id, except_id| user_id == "id" && except_id.is_none())
+            .returning(|_, _| Ok(()));
+
+        let results = execute(Arc::new(repo), Arc::new(sessions), "id".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_for_unknown_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_delete()
+            .times(1)
+            .returning(|_| Err(user::DeleteError::NotFound));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            "id".to_string(),
+        )
+        .await;
+
+        assert_eq!(results, Err(DeleteUserError::NotFound));
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::repositories::{session, user};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum DisableUserError {
+    NotFound,
+    Unknown,
+}
+
+/// Blocks the user from signing in and ends every session they already have.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session::Repository>,
+    user_id: String,
+) -> Result<(), DisableUserError> {
+    let results = repo.set_disabled(user_id.clone(), true).await;
+
+    match results {
+        Ok(()) => {}
+        Err(user::SetDisabledError::NotFound) | Err(user::SetDisabledError::InvalidId) => {
+            return Err(DisableUserError::NotFound)
+        }
+        Err(err @ user::SetDisabledError::Database(_)) => {
+            log::error!("Error In disable_user: {}", err);
+            return Err(DisableUserError::Unknown);
+        }
+    };
+
+    match sessions.revoke_all_for_user(user_id, None).await {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            log::error!("Error In disable_user: {}", err);
+            Err(DisableUserError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::{
+        session::MockRepository as MockSessionRepository, user::MockRepository,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_disable_user_and_revoke_sessions() {
+        let mut repo = MockRepository::new();
+        repo.expect_set_disabled()
+            .times(1)
+            .withf(|id, disabled| id == "id" && *disabled)
+            .returning(|_, _| Ok(()));
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all_for_user()
+            .times(1)
+            .withf(|user_id, except_id| user_id == "id" && except_id.is_none())
+            .returning(|_, _| Ok(()));
+
+        let results = execute(Arc::new(repo), Arc::new(sessions), "id".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_for_unknown_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_set_disabled()
+            .times(1)
+            .returning(|_, _| Err(user::SetDisabledError::NotFound));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            "id".to_string(),
+        )
+        .await;
+
+        assert_eq!(results, Err(DisableUserError::NotFound));
+    }
+}
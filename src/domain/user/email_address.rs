@@ -0,0 +1,15 @@
+/// Canonical form emails are stored and looked up in, so `Someone@Example.com ` and
+/// `someone@example.com` are the same account.
+pub fn normalize(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_trim_and_lowercase() {
+        assert_eq!(normalize("  Someone@Example.COM\n"), "someone@example.com");
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    mailer::{Email, Mailer},
+    repositories::one_time_token,
+};
+
+use super::{
+    entities::{TokenPurpose, User},
+    secret_token,
+};
+
+const VERIFICATION_TOKEN_TTL_HOURS: i64 = 24;
+
+/// Emails the user a fresh token proving they own their address, replacing any sent before.
+pub async fn send(
+    tokens: &dyn one_time_token::Repository,
+    mailer: &dyn Mailer,
+    user: &User,
+) -> Result<(), ()> {
+    let (token, token_hash) = secret_token::generate();
+
+    tokens
+        .create(one_time_token::CreateInput {
+            user_id: user.id.clone(),
+            purpose: TokenPurpose::EmailVerification,
+            token_hash,
+            expires_at: Utc::now() + Duration::hours(VERIFICATION_TOKEN_TTL_HOURS),
+        })
+        .await
+        .map_err(|err| log::error!("Error In email_verification: {}", err))?;
+
+    mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Use the following token to verify your email. It expires in {} hours.\n\n{}",
+                VERIFICATION_TOKEN_TTL_HOURS, token
+            ),
+        })
+        .await
+        .map_err(|_| ())
+}
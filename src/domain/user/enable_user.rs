@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use crate::repositories::user;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum EnableUserError {
+    NotFound,
+    Unknown,
+}
+
+/// Lets a disabled user sign in again.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    user_id: String,
+) -> Result<(), EnableUserError> {
+    let results = repo.set_disabled(user_id, false).await;
+
+    match results {
+        Ok(()) => Ok(()),
+        Err(user::SetDisabledError::NotFound) | Err(user::SetDisabledError::InvalidId) => {
+            Err(EnableUserError::NotFound)
+        }
+        Err(err @ user::SetDisabledError::Database(_)) => {
+            log::error!("Error In enable_user: {}", err);
+            Err(EnableUserError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::user::MockRepository;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_enable_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_set_disabled()
+            .times(1)
+            .withf(|id, disabled| id == "id" && !*disabled)
+            .returning(|_, _| Ok(()));
+
+        let results = execute(Arc::new(repo), "id".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use crate::repositories::user;
+
+use super::{entities::TwoFactor, totp};
+
+/// Name authenticator apps show next to the account
+const ISSUER: &str = "graphql_server";
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Enrollment {
+    pub secret: String,
+    pub uri: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum EnrollTotpError {
+    NotFound,
+    AlreadyEnabled,
+    Unknown,
+}
+
+/// Hands out a new TOTP secret, which only takes effect once `confirm_totp` sees a code from it.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    user_id: String,
+) -> Result<Enrollment, EnrollTotpError> {
+    let user = match repo.find_by_id(user_id).await {
+        Ok(user) => user,
+        Err(user::FindByIdError::NotFound) | Err(user::FindByIdError::InvalidId) => {
+            return Err(EnrollTotpError::NotFound)
+        }
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In enroll_totp: {}", err);
+            return Err(EnrollTotpError::Unknown);
+        }
+    };
+
+    if user.two_factor.is_enabled() {
+        return Err(EnrollTotpError::AlreadyEnabled);
+    }
+
+    let secret = totp::generate_secret();
+
+    let results = repo
+        .set_two_factor(
+            user.id,
+            TwoFactor {
+                pending_secret: Some(secret.clone()),
+                ..user.two_factor
+            },
+        )
+        .await;
+
+    match results {
+        Ok(()) => Ok(Enrollment {
+            uri: totp::provisioning_uri(&secret, &user.email, ISSUER),
+            secret,
+        }),
+        Err(user::SetTwoFactorError::NotFound) | Err(user::SetTwoFactorError::InvalidId) => {
+            Err(EnrollTotpError::NotFound)
+        }
+        Err(err @ user::SetTwoFactorError::Database(_)) => {
+            log::error!("Error In enroll_totp: {}", err);
+            Err(EnrollTotpError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{domain::user::entities::User, repositories::user::MockRepository};
+
+    use super::*;
+
+    fn stub_user(two_factor: TwoFactor) -> User {
+        User::builder()
+            .email("email@example.com")
+            .password("hash")
+            .two_factor(two_factor)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn should_store_pending_secret() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id()
+            .times(1)
+            .returning(|_| Ok(stub_user(TwoFactor::default())));
+        repo.expect_set_two_factor()
+            .times(1)
+            .withf(|id, two_factor| {
+                id == "id" && two_factor.pending_secret.is_some() && two_factor.secret.is_none()
+            })
+            .returning(|_, _| Ok(()));
+
+        let results = execute(Arc::new(repo), "id".to_string()).await;
+
+        match results {
+            Ok(enrollment) => assert!(enrollment.uri.starts_with(&format!(
+                "otpauth://totp/{}:email@example.com?secret={}&",
+                ISSUER, enrollment.secret
+            ))),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_already_enabled_error_when_two_factor_is_on() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id().times(1).returning(|_| {
+            Ok(stub_user(TwoFactor {
+                secret: Some("SECRET".to_string()),
+                ..TwoFactor::default()
+            }))
+        });
+
+        let results = execute(Arc::new(repo), "id".to_string()).await;
+
+        assert_eq!(results, Err(EnrollTotpError::AlreadyEnabled));
+    }
+}
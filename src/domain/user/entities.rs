@@ -1,6 +1,151 @@
+use chrono::{DateTime, Utc};
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct User {
     pub id: String,
     pub email: String,
     pub password: String,
+    /// When the user proved they own `email`, if they have
+    pub verified_at: Option<DateTime<Utc>>,
+    pub roles: Vec<Role>,
+    /// Disabled users can't sign in until an admin enables them again
+    pub disabled: bool,
+    pub created_at: DateTime<Utc>,
+    /// Last time anything about the user was written
+    pub updated_at: DateTime<Utc>,
+    pub two_factor: TwoFactor,
+}
+
+/// Builds users for tests, so each test only spells out the fields it is about.
+#[cfg(test)]
+pub struct UserBuilder {
+    user: User,
+}
+
+#[cfg(test)]
+impl User {
+    pub fn builder() -> UserBuilder {
+        let now = Utc::now();
+
+        UserBuilder {
+            user: User {
+                id: "id".to_string(),
+                email: "email".to_string(),
+                password: "password".to_string(),
+                verified_at: None,
+                roles: Vec::new(),
+                disabled: false,
+                created_at: now,
+                updated_at: now,
+                two_factor: TwoFactor::default(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+impl UserBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.user.id = id.into();
+        self
+    }
+
+    pub fn email(mut self, email: impl Into<String>) -> Self {
+        self.user.email = email.into();
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.user.password = password.into();
+        self
+    }
+
+    pub fn verified_at(mut self, verified_at: DateTime<Utc>) -> Self {
+        self.user.verified_at = Some(verified_at);
+        self
+    }
+
+    pub fn roles(mut self, roles: Vec<Role>) -> Self {
+        self.user.roles = roles;
+        self
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.user.disabled = true;
+        self
+    }
+
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.user.created_at = created_at;
+        self.user.updated_at = created_at;
+        self
+    }
+
+    pub fn two_factor(mut self, two_factor: TwoFactor) -> Self {
+        self.user.two_factor = two_factor;
+        self
+    }
+
+    pub fn build(self) -> User {
+        self.user
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Role {
+    User,
+    Admin,
+}
+
+/// TOTP second factor settings, all empty for users who never enrolled.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct TwoFactor {
+    /// Base32 secret handed out by an enrollment that hasn't been confirmed with a code yet
+    pub pending_secret: Option<String>,
+    /// Base32 secret codes are checked against once enrollment is confirmed
+    pub secret: Option<String>,
+    /// Hashes of the recovery codes that haven't been used yet
+    pub recovery_code_hashes: Vec<String>,
+    /// Time step of the last code accepted, as no code is accepted twice
+    pub last_totp_step: Option<i64>,
+}
+
+impl TwoFactor {
+    pub fn is_enabled(&self) -> bool {
+        self.secret.is_some()
+    }
+}
+
+/// A signed-in session, tracked as a family of refresh tokens where only the latest is valid.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub user_id: String,
+    pub refresh_token_hash: String,
+    pub revoked: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    SignInChallenge,
+}
+
+/// A single-use token handed to a user out of band, e.g. by email.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct OneTimeToken {
+    pub id: String,
+    pub user_id: String,
+    pub purpose: TokenPurpose,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Failed sign-ins counted against an account or email since its last successful sign-in.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SignInAttempts {
+    pub failures: u32,
+    pub locked_until: Option<DateTime<Utc>>,
 }
@@ -14,14 +14,13 @@ pub async fn execute(repo: Arc<dyn user::Repository>, id: String) -> Result<User
     let result = repo.find_by_id(id).await;
 
     match result {
-        Ok(user) => Ok(User {
-            id: user.id,
-            email: user.email,
-            password: user.password,
-        }),
+        Ok(user) => Ok(user),
         Err(user::FindByIdError::NotFound) => Err(FindOneError::NotFound),
         Err(user::FindByIdError::InvalidId) => Err(FindOneError::InvalidId),
-        Err(user::FindByIdError::Unknown) => Err(FindOneError::Unknown),
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In find_one: {}", err);
+            Err(FindOneError::Unknown)
+        }
     }
 }
 
@@ -33,11 +32,7 @@ mod tests {
 
     #[tokio::test]
     async fn should_return_user() {
-        let stub_user = User {
-            id: "id".to_string(),
-            email: "email".to_string(),
-            password: "password".to_string(),
-        };
+        let stub_user = User::builder().build();
         let stub_user_2 = stub_user.clone();
         let mut repo = MockRepository::new();
         repo.expect_find_by_id()
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use crate::{
+    mailer::Mailer,
+    repositories::{one_time_token, session, user},
+};
+
+use super::{hash_password, password_reset, secret_token};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ForcePasswordResetError {
+    NotFound,
+    Unknown,
+}
+
+/// Locks the user out of their current password and sessions until they follow the reset email.
+///
+/// The password is swapped for the hash of a random secret nobody ever sees, so the old one
+/// stops working straight away.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    params: Arc<hash_password::Params>,
+    user_id: String,
+) -> Result<(), ForcePasswordResetError> {
+    let user = match repo.find_by_id(user_id).await {
+        Ok(user) => user,
+        Err(user::FindByIdError::NotFound) | Err(user::FindByIdError::InvalidId) => {
+            return Err(ForcePasswordResetError::NotFound)
+        }
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In force_password_reset: {}", err);
+            return Err(ForcePasswordResetError::Unknown);
+        }
+    };
+
+    let (secret, _) = secret_token::generate();
+    let hashed_password = match hash_password::spawn_execute(secret, *params).await {
+        Ok(hash) => hash,
+        Err(_) => return Err(ForcePasswordResetError::Unknown),
+    };
+
+    match repo.update_password(user.id.clone(), hashed_password).await {
+        Ok(()) => {}
+        Err(user::UpdatePasswordError::NotFound) | Err(user::UpdatePasswordError::InvalidId) => {
+            return Err(ForcePasswordResetError::NotFound)
+        }
+        Err(err @ user::UpdatePasswordError::Database(_)) => {
+            log::error!("Error In force_password_reset: {}", err);
+            return Err(ForcePasswordResetError::Unknown);
+        }
+    };
+
+    match sessions.revoke_all_for_user(user.id.clone(), None).await {
+        Ok(()) => {}
+        Err(err) => {
+            log::error!("Error In force_password_reset: {}", err);
+            return Err(ForcePasswordResetError::Unknown);
+        }
+    };
+
+    password_reset::send(tokens.as_ref(), mailer.as_ref(), &user)
+        .await
+        .map_err(|_| ForcePasswordResetError::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        domain::user::entities::{OneTimeToken, TokenPurpose, User},
+        mailer::MockMailer,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository,
+            session::MockRepository as MockSessionRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    fn params() -> Arc<hash_password::Params> {
+        Arc::new(hash_password::Params {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn should_replace_password_revoke_sessions_and_email_reset_token() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id()
+            .times(1)
+            .returning(|id| Ok(User::builder().id(id).password("old").build()));
+        repo.expect_update_password()
+            .times(1)
+            .withf(|id, password| id == "id" && password != "old")
+            .returning(|_, _| Ok(()));
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all_for_user()
+            .times(1)
+            .withf(|user_id, except_id| user_id == "id" && except_id.is_none())
+            .returning(|_, _| Ok(()));
+
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_create()
+            .times(1)
+            .withf(|input| input.user_id == "id" && input.purpose == TokenPurpose::PasswordReset)
+            .returning(|input| {
+                Ok(OneTimeToken {
+                    id: "token".to_string(),
+                    user_id: input.user_id,
+                    purpose: input.purpose,
+                    created_at: Utc::now(),
+                    expires_at: input.expires_at,
+                })
+            });
+
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
+            .times(1)
+            .withf(|email| email.to == "email")
+            .returning(|_| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(sessions),
+            Arc::new(tokens),
+            Arc::new(mailer),
+            params(),
+            "id".to_string(),
+        )
+        .await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_for_unknown_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id()
+            .times(1)
+            .returning(|_| Err(user::FindByIdError::NotFound));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            params(),
+            "id".to_string(),
+        )
+        .await;
+
+        assert_eq!(results, Err(ForcePasswordResetError::NotFound));
+    }
+}
@@ -1,9 +1,192 @@
-use argon2;
+use argon2::{self, Variant, Version};
+use rand::RngCore;
 
-pub fn execute(password: String) -> Result<String, ()> {
-    let config = argon2::Config::default();
+const SALT_LENGTH: usize = 16;
+// Unpadded base64 of the fixed salt every password was hashed with before salts were random
+const LEGACY_SALT: &str = "dGVzdFNhbHQ";
 
-    let hashed_password = argon2::hash_encoded(password.as_bytes(), "testSalt".as_bytes(), &config);
+/// Argon2id cost parameters used for new password hashes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+pub fn execute(password: String, params: &Params) -> Result<String, ()> {
+    let config = argon2::Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: params.memory_kib,
+        time_cost: params.iterations,
+        lanes: params.parallelism,
+        ..argon2::Config::default()
+    };
+
+    let mut salt = [0u8; SALT_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let hashed_password = argon2::hash_encoded(password.as_bytes(), &salt, &config);
 
     hashed_password.map_err(|_| ())
 }
+
+/// Checks a password against an encoded hash, using the salt and parameters stored in the hash.
+pub fn verify(password: &str, hashed_password: &str) -> Result<bool, ()> {
+    argon2::verify_encoded(hashed_password, password.as_bytes()).map_err(|_| ())
+}
+
+/// Runs `execute` on the blocking thread pool, since hashing takes long enough to hold up every
+/// other request waiting on the same worker.
+pub async fn spawn_execute(password: String, params: Params) -> Result<String, ()> {
+    tokio::task::spawn_blocking(move || execute(password, &params))
+        .await
+        .map_err(|_| ())?
+}
+
+/// Runs `verify` on the blocking thread pool, for the same reason as `spawn_execute`.
+pub async fn spawn_verify(password: String, hashed_password: String) -> Result<bool, ()> {
+    tokio::task::spawn_blocking(move || verify(&password, &hashed_password))
+        .await
+        .map_err(|_| ())?
+}
+
+/// Whether an encoded hash was produced with a legacy salt, another variant or cheaper
+/// parameters than `params`, meaning it should be replaced once the plaintext is known.
+pub fn needs_rehash(hashed_password: &str, params: &Params) -> bool {
+    let mut parts = hashed_password.split('$').skip(1);
+
+    if parts.next() != Some("argon2id") || parts.next() != Some("v=19") {
+        return true;
+    }
+
+    let (mut memory_kib, mut iterations, mut parallelism) = (0, 0, 0);
+    for param in parts.next().unwrap_or_default().split(',') {
+        let value = match param.split_once('=') {
+            Some((name, value)) => (name, value.parse::<u32>().unwrap_or(0)),
+            None => return true,
+        };
+
+        match value {
+            ("m", value) => memory_kib = value,
+            ("t", value) => iterations = value,
+            ("p", value) => parallelism = value,
+            _ => return true,
+        }
+    }
+
+    if parts.next() == Some(LEGACY_SALT) {
+        return true;
+    }
+
+    memory_kib < params.memory_kib
+        || iterations < params.iterations
+        || parallelism != params.parallelism
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: Params = Params {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn should_verify_hashed_password() {
+        let hashed_password = execute("pass".to_string(), &PARAMS).unwrap();
+
+        assert!(hashed_password.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert_eq!(verify("pass", &hashed_password), Ok(true));
+        assert_eq!(verify("other", &hashed_password), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn should_not_hold_up_worker_while_hashing() {
+        let hashing = spawn_execute("pass".to_string(), Params::default());
+        tokio::pin!(hashing);
+
+        // The runtime has a single worker, so the timer only fires first if hashing left it free
+        tokio::select! {
+            _ = &mut hashing => panic!("hashing finished before a 1ms timer"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(1)) => {}
+        }
+        assert!(verify("pass", &hashing.await.unwrap()).unwrap());
+    }
+
+    #[test]
+    fn should_salt_every_hash() {
+        let first = execute("pass".to_string(), &PARAMS).unwrap();
+        let second = execute("pass".to_string(), &PARAMS).unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn should_verify_legacy_fixed_salt_hash() {
+        let legacy =
+            argon2::hash_encoded(b"pass", b"testSalt", &argon2::Config::default()).unwrap();
+
+        assert_eq!(verify("pass", &legacy), Ok(true));
+        assert_eq!(verify("other", &legacy), Ok(false));
+    }
+
+    #[test]
+    fn should_not_rehash_with_same_params() {
+        let hashed_password = execute("pass".to_string(), &PARAMS).unwrap();
+
+        assert!(!needs_rehash(&hashed_password, &PARAMS));
+    }
+
+    #[test]
+    fn should_rehash_when_params_are_stronger() {
+        let hashed_password = execute("pass".to_string(), &PARAMS).unwrap();
+
+        assert!(needs_rehash(
+            &hashed_password,
+            &Params {
+                memory_kib: 2048,
+                ..PARAMS
+            }
+        ));
+        assert!(needs_rehash(
+            &hashed_password,
+            &Params {
+                iterations: 2,
+                ..PARAMS
+            }
+        ));
+    }
+
+    #[test]
+    fn should_rehash_legacy_hashes() {
+        let legacy =
+            argon2::hash_encoded(b"pass", b"testSalt", &argon2::Config::default()).unwrap();
+        let legacy_id = argon2::hash_encoded(
+            b"pass",
+            b"testSalt",
+            &argon2::Config {
+                variant: Variant::Argon2id,
+                mem_cost: PARAMS.memory_kib,
+                time_cost: PARAMS.iterations,
+                ..argon2::Config::default()
+            },
+        )
+        .unwrap();
+
+        assert!(needs_rehash(&legacy, &PARAMS));
+        assert!(needs_rehash(&legacy_id, &PARAMS));
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use crate::repositories::user::{self, Cursor, ListFilter, ListOrder};
+
+use super::entities::User;
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+pub struct Input {
+    pub filter: ListFilter,
+    pub order: ListOrder,
+    pub after: Option<Cursor>,
+    pub before: Option<Cursor>,
+    pub first: Option<usize>,
+    pub last: Option<usize>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Page {
+    pub users: Vec<(Cursor, User)>,
+    pub has_previous_page: bool,
+    pub has_next_page: bool,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ListUsersError {
+    /// Paging from both ends at once is ambiguous, so only one of `first` and `last` is allowed
+    FirstAndLast,
+    InvalidCursor,
+    Unknown,
+}
+
+/// Returns one page of users between the cursors, taking `first` from the start of the range or
+/// `last` from its end.
+///
+/// One user more than asked for is fetched to tell whether the range continues past the page.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    input: Input,
+) -> Result<Page, ListUsersError> {
+    let Input {
+        filter,
+        order,
+        after,
+        before,
+        first,
+        last,
+    } = input;
+
+    if first.is_some() && last.is_some() {
+        return Err(ListUsersError::FirstAndLast);
+    }
+
+    let reverse = last.is_some();
+    let size = first
+        .or(last)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE);
+    let (has_after, has_before) = (after.is_some(), before.is_some());
+
+    let results = repo
+        .list(user::ListInput {
+            filter,
+            order,
+            after,
+            before,
+            reverse,
+            limit: size + 1,
+        })
+        .await;
+
+    let mut users = match results {
+        Ok(users) => users,
+        Err(user::ListError::InvalidCursor) => return Err(ListUsersError::InvalidCursor),
+        Err(err @ user::ListError::Database(_)) => {
+            log::error!("Error In list_users: {}", err);
+            return Err(ListUsersError::Unknown);
+        }
+    };
+
+    let has_more = users.len() > size;
+    users.truncate(size);
+
+    if reverse {
+        users.reverse();
+        Ok(Page {
+            users,
+            has_previous_page: has_more,
+            has_next_page: has_before,
+        })
+    } else {
+        Ok(Page {
+            users,
+            has_previous_page: has_after,
+            has_next_page: has_more,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::repositories::user::MockRepository;
+
+    use super::*;
+
+    fn listed(index: i64) -> (Cursor, User) {
+        let id = format!("id{}", index);
+        let created_at = Utc.timestamp(index, 0);
+        (
+            Cursor {
+                created_at,
+                id: id.clone(),
+            },
+            User::builder()
+                .id(id)
+                .password("hash")
+                .created_at(created_at)
+                .build(),
+        )
+    }
+
+    fn input(first: Option<usize>, last: Option<usize>) -> Input {
+        Input {
+            filter: ListFilter::default(),
+            order: ListOrder::CreatedAtAsc,
+            after: None,
+            before: None,
+            first,
+            last,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_fetch_one_extra_user_to_detect_next_page() {
+        let mut repo = MockRepository::new();
+        repo.expect_list()
+            .times(1)
+            .withf(|input| input.limit == 3 && !input.reverse)
+            .returning(|_| Ok((0..3).map(listed).collect()));
+
+        let results = execute(Arc::new(repo), input(Some(2), None)).await;
+
+        match results {
+            Ok(page) => {
+                assert_eq!(page.users, (0..2).map(listed).collect::<Vec<_>>());
+                assert!(page.has_next_page);
+                assert!(!page.has_previous_page);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_walk_backwards_for_last_and_restore_order() {
+        let mut repo = MockRepository::new();
+        repo.expect_list()
+            .times(1)
+            .withf(|input| input.limit == 3 && input.reverse)
+            .returning(|_| Ok(vec![listed(9), listed(8)]));
+
+        let results = execute(
+            Arc::new(repo),
+            Input {
+                before: Some(listed(10).0),
+                ..input(None, Some(2))
+            },
+        )
+        .await;
+
+        match results {
+            Ok(page) => {
+                assert_eq!(page.users, vec![listed(8), listed(9)]);
+                assert!(!page.has_previous_page);
+                assert!(page.has_next_page);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_cap_page_size() {
+        let mut repo = MockRepository::new();
+        repo.expect_list()
+            .times(1)
+            .withf(|input| input.limit == MAX_PAGE_SIZE + 1)
+            .returning(|_| Ok(Vec::new()));
+
+        let results = execute(Arc::new(repo), input(Some(1000), None)).await;
+
+        assert!(results.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_reject_first_and_last_together() {
+        let results = execute(Arc::new(MockRepository::new()), input(Some(1), Some(1))).await;
+
+        assert_eq!(results, Err(ListUsersError::FirstAndLast));
+    }
+}
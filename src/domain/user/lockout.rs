@@ -0,0 +1,190 @@
+use chrono::{Duration, Utc};
+
+use crate::repositories::sign_in_attempt::{self, Subject};
+
+/// Longest a subject is ever locked for, however many times it keeps failing
+const MAX_LOCKOUT_HOURS: i64 = 24;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// Failed sign-ins allowed before a subject gets locked
+    pub max_failed_attempts: u32,
+    /// How long the first lock lasts, doubling with every lock after it
+    pub duration: Duration,
+    /// Failures are forgotten once none has happened for this long
+    pub failure_window: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_failed_attempts: 5,
+            duration: Duration::minutes(15),
+            failure_window: Duration::hours(24),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum LockoutError {
+    Locked,
+    Unknown,
+}
+
+/// Fails while the subject is locked; locks expire on their own once their cooldown passes.
+pub async fn check(
+    attempts: &dyn sign_in_attempt::Repository,
+    subject: Subject,
+) -> Result<(), LockoutError> {
+    match attempts.find(subject).await {
+        Ok(Some(record)) if record.locked_until.is_some_and(|until| until > Utc::now()) => {
+            Err(LockoutError::Locked)
+        }
+        Ok(_) => Ok(()),
+        Err(err) => {
+            log::error!("Error In lockout: {}", err);
+            Err(LockoutError::Unknown)
+        }
+    }
+}
+
+/// Counts a failed sign-in against the subject, locking it every time it reaches another multiple
+/// of the threshold. Once a lock runs out it takes as many failures again to be locked, for twice as
+/// long.
+///
+/// Failing to record is only logged, as the sign-in has failed either way.
+pub async fn record_failure(
+    attempts: &dyn sign_in_attempt::Repository,
+    settings: &Settings,
+    subject: Subject,
+) {
+    let since = Utc::now() - settings.failure_window;
+    let record = match attempts.record_failure(subject.clone(), since).await {
+        Ok(record) => record,
+        Err(err) => {
+            log::error!("Error In lockout for {:?}: {}", subject, err);
+            return;
+        }
+    };
+
+    if record.failures % settings.max_failed_attempts != 0 {
+        return;
+    }
+
+    let until = Utc::now() + lock_duration(settings, record.failures);
+    if let Err(err) = attempts.lock(subject.clone(), until).await {
+        log::error!("Error In lockout for {:?}: {}", subject, err);
+    }
+}
+
+/// Forgets the subject's failures after a successful sign-in.
+pub async fn clear(attempts: &dyn sign_in_attempt::Repository, subject: Subject) {
+    if let Err(err) = attempts.clear(subject.clone()).await {
+        log::error!("Error In lockout for {:?}: {}", subject, err);
+    }
+}
+
+fn lock_duration(settings: &Settings, failures: u32) -> Duration {
+    let doublings = (failures / settings.max_failed_attempts - 1).min(16);
+    let seconds = settings
+        .duration
+        .num_seconds()
+        .saturating_mul(1 << doublings);
+
+    Duration::seconds(seconds.min(MAX_LOCKOUT_HOURS * 60 * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        domain::user::entities::SignInAttempts, repositories::sign_in_attempt::MockRepository,
+    };
+
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            max_failed_attempts: 3,
+            duration: Duration::minutes(1),
+            failure_window: Duration::hours(1),
+        }
+    }
+
+    #[test]
+    fn should_double_lock_duration_up_to_a_cap() {
+        assert_eq!(lock_duration(&settings(), 3), Duration::minutes(1));
+        assert_eq!(lock_duration(&settings(), 6), Duration::minutes(2));
+        assert_eq!(lock_duration(&settings(), 12), Duration::minutes(8));
+        assert_eq!(
+            lock_duration(&settings(), 100),
+            Duration::hours(MAX_LOCKOUT_HOURS)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_lock_once_threshold_is_reached() {
+        let mut attempts = MockRepository::new();
+        attempts
+            .expect_record_failure()
+            .times(1)
+            .withf(|_, since| *since < Utc::now() - Duration::minutes(59))
+            .returning(|_, _| {
+                Ok(SignInAttempts {
+                    failures: 3,
+                    locked_until: None,
+                })
+            });
+        attempts
+            .expect_lock()
+            .times(1)
+            .withf(|subject, until| {
+                *subject == Subject::Account("id".to_string()) && *until > Utc::now()
+            })
+            .returning(|_, _| Ok(()));
+
+        record_failure(&attempts, &settings(), Subject::Account("id".to_string())).await;
+    }
+
+    #[tokio::test]
+    async fn should_not_lock_below_threshold() {
+        let mut attempts = MockRepository::new();
+        attempts.expect_record_failure().times(1).returning(|_, _| {
+            Ok(SignInAttempts {
+                failures: 2,
+                locked_until: None,
+            })
+        });
+        attempts.expect_lock().never();
+
+        record_failure(&attempts, &settings(), Subject::Account("id".to_string())).await;
+    }
+
+    #[tokio::test]
+    async fn should_not_lock_again_on_first_failure_after_lock_expired() {
+        let mut attempts = MockRepository::new();
+        attempts.expect_record_failure().times(1).returning(|_, _| {
+            Ok(SignInAttempts {
+                failures: 4,
+                locked_until: Some(Utc::now() - Duration::seconds(1)),
+            })
+        });
+        attempts.expect_lock().never();
+
+        record_failure(&attempts, &settings(), Subject::Account("id".to_string())).await;
+    }
+
+    #[tokio::test]
+    async fn should_unlock_once_cooldown_has_passed() {
+        let mut attempts = MockRepository::new();
+        attempts.expect_find().times(1).returning(|_| {
+            Ok(Some(SignInAttempts {
+                failures: 3,
+                locked_until: Some(Utc::now() - Duration::seconds(1)),
+            }))
+        });
+
+        let results = check(&attempts, Subject::Email("email".to_string())).await;
+
+        assert_eq!(results, Ok(()));
+    }
+}
@@ -1,5 +1,34 @@
+pub mod access_token;
+pub mod admin_create_user;
+pub mod change_password;
+pub mod confirm_totp;
+pub mod delete_user;
+pub mod disable_user;
+pub mod email_address;
+pub mod email_verification;
+pub mod enable_user;
+pub mod enroll_totp;
 pub mod entities;
 pub mod find_one;
-mod hash_password;
+pub mod force_password_reset;
+pub mod hash_password;
+pub mod list_users;
+pub mod lockout;
+pub mod password_policy;
+pub mod password_reset;
+pub mod recovery_codes;
+pub mod refresh_session;
 pub mod register;
+pub mod request_password_reset;
+pub mod resend_verification;
+pub mod reset_password;
+pub mod secret_token;
+pub mod session;
+pub mod set_roles;
 pub mod sign_in;
+pub mod sign_out;
+pub mod sign_out_everywhere;
+pub mod totp;
+pub mod unlock_account;
+pub mod verify_email;
+pub mod verify_totp;
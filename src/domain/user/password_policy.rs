@@ -0,0 +1,42 @@
+const MIN_LENGTH: usize = 8;
+const MAX_LENGTH: usize = 128;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum PolicyError {
+    TooShort,
+    TooLong,
+}
+
+pub fn validate(password: &str) -> Result<(), PolicyError> {
+    let length = password.chars().count();
+
+    if length < MIN_LENGTH {
+        return Err(PolicyError::TooShort);
+    }
+
+    if length > MAX_LENGTH {
+        return Err(PolicyError::TooLong);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_accept_password_within_bounds() {
+        assert_eq!(validate("password"), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_short_password() {
+        assert_eq!(validate("pass"), Err(PolicyError::TooShort));
+    }
+
+    #[test]
+    fn should_reject_long_password() {
+        assert_eq!(validate(&"a".repeat(129)), Err(PolicyError::TooLong));
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    mailer::{Email, Mailer},
+    repositories::one_time_token,
+};
+
+use super::{
+    entities::{TokenPurpose, User},
+    secret_token,
+};
+
+const RESET_TOKEN_TTL_MINUTES: i64 = 60;
+
+/// Emails the user a fresh token for `reset_password`, replacing any sent before.
+pub async fn send(
+    tokens: &dyn one_time_token::Repository,
+    mailer: &dyn Mailer,
+    user: &User,
+) -> Result<(), ()> {
+    let (token, token_hash) = secret_token::generate();
+
+    tokens
+        .create(one_time_token::CreateInput {
+            user_id: user.id.clone(),
+            purpose: TokenPurpose::PasswordReset,
+            token_hash,
+            expires_at: Utc::now() + Duration::minutes(RESET_TOKEN_TTL_MINUTES),
+        })
+        .await
+        .map_err(|err| log::error!("Error In password_reset: {}", err))?;
+
+    mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Use the following token to reset your password. It expires in {} minutes.\n\n{}",
+                RESET_TOKEN_TTL_MINUTES, token
+            ),
+        })
+        .await
+        .map_err(|_| ())
+}
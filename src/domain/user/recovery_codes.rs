@@ -0,0 +1,54 @@
+use rand::Rng;
+
+use super::secret_token;
+
+const COUNT: usize = 10;
+const LENGTH: usize = 10;
+// Lowercase letters and digits without the easily confused l, o, 0 and 1
+const ALPHABET: &[u8; 32] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+/// Generates a fresh set of recovery codes, formatted like `abcde-fghij`, along with the hashes
+/// that get persisted for them.
+pub fn generate() -> Vec<(String, String)> {
+    let mut rng = rand::thread_rng();
+
+    (0..COUNT)
+        .map(|_| {
+            let mut code: String = (0..LENGTH)
+                .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                .collect();
+            code.insert(LENGTH / 2, '-');
+
+            let hash = hash(&code);
+            (code, hash)
+        })
+        .collect()
+}
+
+/// Hashes a recovery code as typed by the user, ignoring case, spaces and dashes.
+pub fn hash(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|char| !matches!(char, '-' | ' '))
+        .map(|char| char.to_ascii_lowercase())
+        .collect();
+
+    secret_token::hash(&normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_hash_codes_regardless_of_formatting() {
+        let codes = generate();
+
+        assert_eq!(codes.len(), COUNT);
+        for (code, code_hash) in &codes {
+            assert_eq!(code.len(), LENGTH + 1);
+            assert_eq!(hash(code), *code_hash);
+            assert_eq!(hash(&code.replace('-', "").to_uppercase()), *code_hash);
+        }
+    }
+}
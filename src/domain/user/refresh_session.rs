@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+
+use crate::repositories::session as repository;
+
+use super::{access_token, secret_token, session::Tokens};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum RefreshSessionError {
+    Invalid,
+    Expired,
+    Reused,
+    Unknown,
+}
+
+pub async fn execute(
+    repo: Arc<dyn repository::Repository>,
+    keys: Arc<access_token::Keys>,
+    refresh_token: String,
+) -> Result<Tokens, RefreshSessionError> {
+    let refresh_token_hash = secret_token::hash(&refresh_token);
+
+    let current = repo
+        .find_by_refresh_token_hash(refresh_token_hash.clone())
+        .await;
+
+    let current = match current {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(RefreshSessionError::Invalid),
+        Err(err) => {
+            log::error!("Error In refresh_session: {}", err);
+            return Err(RefreshSessionError::Unknown);
+        }
+    };
+
+    if current.revoked {
+        return Err(RefreshSessionError::Invalid);
+    }
+
+    if current.expires_at <= Utc::now() {
+        return Err(RefreshSessionError::Expired);
+    }
+
+    // A rotated token being presented again means it leaked, so the whole family is revoked
+    if current.refresh_token_hash != refresh_token_hash {
+        return Err(revoke(repo.as_ref(), current.id).await);
+    }
+
+    let (new_refresh_token, new_refresh_token_hash) = secret_token::generate();
+
+    let results = repo
+        .rotate(
+            current.id.clone(),
+            refresh_token_hash,
+            new_refresh_token_hash,
+        )
+        .await;
+
+    match results {
+        Ok(()) => {}
+        Err(repository::RotateError::Stale) => return Err(revoke(repo.as_ref(), current.id).await),
+        Err(err) => {
+            log::error!("Error In refresh_session: {}", err);
+            return Err(RefreshSessionError::Unknown);
+        }
+    };
+
+    let access_token = match access_token::issue(&keys, &current.user_id, &current.id) {
+        Ok(token) => token,
+        Err(_) => return Err(RefreshSessionError::Unknown),
+    };
+
+    Ok(Tokens {
+        access_token,
+        refresh_token: new_refresh_token,
+    })
+}
+
+async fn revoke(repo: &dyn repository::Repository, id: String) -> RefreshSessionError {
+    match repo.revoke(id).await {
+        Ok(()) => RefreshSessionError::Reused,
+        Err(err) => {
+            log::error!("Error In refresh_session: {}", err);
+            RefreshSessionError::Unknown
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{domain::user::entities::Session, repositories::session::MockRepository};
+
+    use super::*;
+
+    fn keys() -> Arc<access_token::Keys> {
+        Arc::new(access_token::Keys::from_secret(
+            b"secret",
+            Duration::minutes(15),
+        ))
+    }
+
+    fn stub_session(refresh_token: &str) -> Session {
+        Session {
+            id: "session".to_string(),
+            user_id: "user".to_string(),
+            refresh_token_hash: secret_token::hash(refresh_token),
+            revoked: false,
+            expires_at: Utc::now() + Duration::days(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_rotate_current_refresh_token() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_refresh_token_hash()
+            .times(1)
+            .returning(|_| Ok(Some(stub_session("token"))));
+        repo.expect_rotate()
+            .times(1)
+            .withf(|id, current, new| {
+                id == "session" && *current == secret_token::hash("token") && current != new
+            })
+            .returning(|_, _, _| Ok(()));
+
+        let results = execute(Arc::new(repo), keys(), "token".to_string()).await;
+
+        match results {
+            Ok(tokens) => {
+                assert_ne!(tokens.refresh_token, "token");
+                assert_eq!(
+                    access_token::verify(&keys(), &tokens.access_token.token)
+                        .unwrap()
+                        .sub,
+                    "user"
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_revoke_session_when_rotated_token_is_reused() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_refresh_token_hash()
+            .times(1)
+            .returning(|_| Ok(Some(stub_session("newer-token"))));
+        repo.expect_revoke()
+            .times(1)
+            .withf(|id| id == "session")
+            .returning(|_| Ok(()));
+
+        let results = execute(Arc::new(repo), keys(), "token".to_string()).await;
+
+        assert_eq!(results.err(), Some(RefreshSessionError::Reused));
+    }
+
+    #[tokio::test]
+    async fn should_revoke_session_when_rotation_races() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_refresh_token_hash()
+            .times(1)
+            .returning(|_| Ok(Some(stub_session("token"))));
+        repo.expect_rotate()
+            .times(1)
+            .returning(|_, _, _| Err(repository::RotateError::Stale));
+        repo.expect_revoke().times(1).returning(|_| Ok(()));
+
+        let results = execute(Arc::new(repo), keys(), "token".to_string()).await;
+
+        assert_eq!(results.err(), Some(RefreshSessionError::Reused));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_error_for_revoked_session() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_refresh_token_hash()
+            .times(1)
+            .returning(|_| {
+                Ok(Some(Session {
+                    revoked: true,
+                    ..stub_session("token")
+                }))
+            });
+
+        let results = execute(Arc::new(repo), keys(), "token".to_string()).await;
+
+        assert_eq!(results.err(), Some(RefreshSessionError::Invalid));
+    }
+
+    #[tokio::test]
+    async fn should_return_expired_error_for_expired_session() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_refresh_token_hash()
+            .times(1)
+            .returning(|_| {
+                Ok(Some(Session {
+                    expires_at: Utc::now() - Duration::days(1),
+                    ..stub_session("token")
+                }))
+            });
+
+        let results = execute(Arc::new(repo), keys(), "token".to_string()).await;
+
+        assert_eq!(results.err(), Some(RefreshSessionError::Expired));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_error_for_unknown_token() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_refresh_token_hash()
+            .times(1)
+            .returning(|_| Ok(None));
+
+        let results = execute(Arc::new(repo), keys(), "token".to_string()).await;
+
+        assert_eq!(results.err(), Some(RefreshSessionError::Invalid));
+    }
+}
@@ -1,8 +1,15 @@
 use std::sync::Arc;
 
-use crate::repositories::user;
+use crate::{
+    mailer::Mailer,
+    repositories::{one_time_token, user},
+};
 
-use super::{entities::User, hash_password};
+use super::{
+    email_address, email_verification,
+    entities::{Role, User},
+    hash_password, password_policy,
+};
 
 pub struct Input {
     pub email: String,
@@ -15,17 +22,33 @@ pub enum RegisterError {
     Unknown,
 }
 
-pub async fn execute(repo: Arc<dyn user::Repository>, input: Input) -> Result<User, RegisterError> {
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    params: Arc<hash_password::Params>,
+    input: Input,
+) -> Result<User, RegisterError> {
     let Input { email, password } = input;
+
+    let email = email_address::normalize(&email);
+
+    if password_policy::validate(&password).is_err() {
+        return Err(RegisterError::InvalidPassword);
+    }
+
     let previous_user = repo.find_one_by_email(email.clone()).await;
 
     match previous_user {
         Ok(None) => {}
         Ok(Some(_)) => return Err(RegisterError::AlreadyExists),
-        Err(user::FindOneByEmailError::Unknown) => return Err(RegisterError::Unknown),
+        Err(err @ user::FindOneByEmailError::Database(_)) => {
+            log::error!("Error In register: {}", err);
+            return Err(RegisterError::Unknown);
+        }
     };
 
-    let hashed_password = match hash_password::execute(password) {
+    let hashed_password = match hash_password::spawn_execute(password, *params).await {
         Ok(hash) => hash,
         Err(_) => return Err(RegisterError::InvalidPassword),
     };
@@ -34,25 +57,53 @@ pub async fn execute(repo: Arc<dyn user::Repository>, input: Input) -> Result<Us
         .create(user::CreateInput {
             email,
             password: hashed_password,
+            roles: vec![Role::User],
         })
         .await;
 
-    match results {
-        Ok(user) => Ok(User {
-            id: user.id,
-            email: user.email,
-            password: user.password,
-        }),
-        Err(user::CreateError::Unknown) => Err(RegisterError::Unknown),
+    let user = match results {
+        Ok(user) => user,
+        // Someone took the email between the check above and now
+        Err(user::CreateError::AlreadyExists) => return Err(RegisterError::AlreadyExists),
+        Err(err @ user::CreateError::Database(_)) => {
+            log::error!("Error In register: {}", err);
+            return Err(RegisterError::Unknown);
+        }
+    };
+
+    // The account is usable without it, and another email can be asked for with resendVerification
+    if email_verification::send(tokens.as_ref(), mailer.as_ref(), &user)
+        .await
+        .is_err()
+    {
+        log::error!("Error sending verification email to user {}", user.id);
     }
+
+    Ok(user)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::repositories::user::MockRepository;
+    use chrono::Utc;
+
+    use crate::{
+        domain::user::entities::OneTimeToken,
+        mailer::MockMailer,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository, user::MockRepository,
+        },
+    };
 
     use super::*;
 
+    fn params() -> Arc<hash_password::Params> {
+        Arc::new(hash_password::Params {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        })
+    }
+
     #[tokio::test]
     async fn should_return_user() {
         let mut repo = MockRepository::new();
@@ -60,20 +111,37 @@ mod tests {
             .times(1)
             .returning(|_| Ok(None));
 
-        repo.expect_create()
+        repo.expect_create().times(1).returning(
+            |user::CreateInput {
+                 email, password, ..
+             }| { Ok(User::builder().email(email).password(password).build()) },
+        );
+
+        let mut tokens = MockTokenRepository::new();
+        tokens.expect_create().times(1).returning(|input| {
+            Ok(OneTimeToken {
+                id: "token".to_string(),
+                user_id: input.user_id,
+                purpose: input.purpose,
+                created_at: Utc::now(),
+                expires_at: input.expires_at,
+            })
+        });
+
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
             .times(1)
-            .returning(|user::CreateInput { email, password }| {
-                Ok(User {
-                    id: "id".to_string(),
-                    email,
-                    password,
-                })
-            });
+            .withf(|email| email.to == "email")
+            .returning(|_| Ok(()));
 
         let email = "email".to_string();
         let password = "password".to_string();
         let results = execute(
             Arc::new(repo),
+            Arc::new(tokens),
+            Arc::new(mailer),
+            params(),
             Input {
                 email: email.clone(),
                 password: password.clone(),
@@ -82,14 +150,11 @@ mod tests {
         .await;
 
         match results {
-            Ok(user) => assert_eq!(
-                user,
-                User {
-                    id: "id".to_string(),
-                    email,
-                    password: hash_password::execute(password).unwrap()
-                }
-            ),
+            Ok(user) => {
+                assert_eq!(user.id, "id");
+                assert_eq!(user.email, email);
+                assert_eq!(hash_password::verify(&password, &user.password), Ok(true));
+            }
             _ => unreachable!(),
         }
     }
@@ -99,16 +164,15 @@ mod tests {
         let mut repo = MockRepository::new();
         let email = "email".to_string();
         let password = "password".to_string();
-        repo.expect_find_one_by_email().times(1).returning(|email| {
-            Ok(Some(User {
-                id: "id".to_string(),
-                email,
-                password: "pass".to_string(),
-            }))
-        });
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(|email| Ok(Some(User::builder().email(email).password("pass").build())));
 
         let results = execute(
             Arc::new(repo),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            params(),
             Input {
                 email: email.clone(),
                 password: password.clone(),
@@ -121,4 +185,53 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[tokio::test]
+    async fn should_return_already_exists_error_when_create_loses_race() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .withf(|email| email == "someone@example.com")
+            .returning(|_| Ok(None));
+        repo.expect_create()
+            .times(1)
+            .withf(|input| input.email == "someone@example.com")
+            .returning(|_| Err(user::CreateError::AlreadyExists));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            params(),
+            Input {
+                email: " Someone@Example.com ".to_string(),
+                password: "password".to_string(),
+            },
+        )
+        .await;
+
+        assert_eq!(results.err(), Some(RegisterError::AlreadyExists));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_password_error_when_policy_fails() {
+        let repo = MockRepository::new();
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            params(),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Err(error) => assert_eq!(error, RegisterError::InvalidPassword),
+            _ => unreachable!(),
+        }
+    }
 }
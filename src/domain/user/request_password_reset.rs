@@ -0,0 +1,132 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::sleep;
+
+use crate::{
+    mailer::Mailer,
+    repositories::{one_time_token, user},
+};
+
+use super::{email_address, password_reset};
+
+enum RequestPasswordResetError {
+    NotFound,
+    Unknown,
+}
+
+async fn logic(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    email: String,
+) -> Result<(), RequestPasswordResetError> {
+    let user = match repo
+        .find_one_by_email(email_address::normalize(&email))
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(RequestPasswordResetError::NotFound),
+        Err(err @ user::FindOneByEmailError::Database(_)) => {
+            log::error!("Error In request_password_reset: {}", err);
+            return Err(RequestPasswordResetError::Unknown);
+        }
+    };
+
+    password_reset::send(tokens.as_ref(), mailer.as_ref(), &user)
+        .await
+        .map_err(|_| RequestPasswordResetError::Unknown)
+}
+
+/// Emails a password reset token to the account with this email, if there is one.
+///
+/// Responds the same way, and after the same delay as sign in, whether or not the account
+/// exists so it cannot be used to discover registered emails.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    padding: Duration,
+    email: String,
+) {
+    let (_, results) = tokio::join!(sleep(padding), logic(repo, tokens, mailer, email));
+
+    if let Err(RequestPasswordResetError::Unknown) = results {
+        log::error!("Error In request_password_reset");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        domain::user::entities::{OneTimeToken, TokenPurpose, User},
+        mailer::MockMailer,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_email_reset_token_to_existing_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(|email| Ok(Some(User::builder().email(email).build())));
+
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_create()
+            .times(1)
+            .withf(|input| input.user_id == "id" && input.purpose == TokenPurpose::PasswordReset)
+            .returning(|input| {
+                Ok(OneTimeToken {
+                    id: "token".to_string(),
+                    user_id: input.user_id,
+                    purpose: input.purpose,
+                    created_at: Utc::now(),
+                    expires_at: input.expires_at,
+                })
+            });
+
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
+            .times(1)
+            .withf(|email| email.to == "email")
+            .returning(|_| Ok(()));
+
+        execute(
+            Arc::new(repo),
+            Arc::new(tokens),
+            Arc::new(mailer),
+            Duration::ZERO,
+            "email".to_string(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn should_not_email_unknown_user_and_take_as_long() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(|_| Ok(None));
+
+        let padding = Duration::from_millis(50);
+
+        let started = std::time::Instant::now();
+        execute(
+            Arc::new(repo),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            padding,
+            "email".to_string(),
+        )
+        .await;
+
+        assert!(started.elapsed() >= padding);
+    }
+}
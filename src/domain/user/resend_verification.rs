@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use tokio::time::sleep;
+
+use crate::{
+    mailer::Mailer,
+    repositories::{one_time_token, user},
+};
+
+use super::{email_address, email_verification, entities::TokenPurpose};
+
+/// Minimum time between two verification emails for the same user
+const RESEND_INTERVAL_SECONDS: i64 = 60;
+
+enum ResendVerificationError {
+    NotFound,
+    AlreadyVerified,
+    Throttled,
+    Unknown,
+}
+
+async fn logic(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    email: String,
+) -> Result<(), ResendVerificationError> {
+    let user = match repo
+        .find_one_by_email(email_address::normalize(&email))
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(ResendVerificationError::NotFound),
+        Err(err @ user::FindOneByEmailError::Database(_)) => {
+            log::error!("Error In resend_verification: {}", err);
+            return Err(ResendVerificationError::Unknown);
+        }
+    };
+
+    if user.verified_at.is_some() {
+        return Err(ResendVerificationError::AlreadyVerified);
+    }
+
+    let previous = tokens
+        .find_by_user(user.id.clone(), TokenPurpose::EmailVerification)
+        .await;
+
+    match previous {
+        Ok(Some(previous))
+            if previous.created_at + Duration::seconds(RESEND_INTERVAL_SECONDS) > Utc::now() =>
+        {
+            return Err(ResendVerificationError::Throttled)
+        }
+        Ok(_) => {}
+        Err(err) => {
+            log::error!("Error In resend_verification: {}", err);
+            return Err(ResendVerificationError::Unknown);
+        }
+    };
+
+    email_verification::send(tokens.as_ref(), mailer.as_ref(), &user)
+        .await
+        .map_err(|_| ResendVerificationError::Unknown)
+}
+
+/// Emails a new verification token to the unverified account with this email, at most once a
+/// minute.
+///
+/// Like `request_password_reset`, it responds the same way whatever happened so it cannot be used
+/// to discover registered emails.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    mailer: Arc<dyn Mailer>,
+    padding: std::time::Duration,
+    email: String,
+) {
+    let (_, results) = tokio::join!(sleep(padding), logic(repo, tokens, mailer, email));
+
+    if let Err(ResendVerificationError::Unknown) = results {
+        log::error!("Error In resend_verification");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        domain::user::entities::{OneTimeToken, User},
+        mailer::MockMailer,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    fn repo(verified: bool) -> MockRepository {
+        let mut repo = MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(move |email| {
+                let user = User::builder().email(email);
+
+                Ok(Some(if verified {
+                    user.verified_at(Utc::now()).build()
+                } else {
+                    user.build()
+                }))
+            });
+
+        repo
+    }
+
+    fn previous_token(age: Duration) -> Option<OneTimeToken> {
+        Some(OneTimeToken {
+            id: "token".to_string(),
+            user_id: "id".to_string(),
+            purpose: TokenPurpose::EmailVerification,
+            created_at: Utc::now() - age,
+            expires_at: Utc::now() + Duration::hours(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn should_resend_verification_email() {
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_find_by_user()
+            .times(1)
+            .returning(|_, _| Ok(previous_token(Duration::minutes(5))));
+        tokens.expect_create().times(1).returning(|input| {
+            Ok(OneTimeToken {
+                id: "token".to_string(),
+                user_id: input.user_id,
+                purpose: input.purpose,
+                created_at: Utc::now(),
+                expires_at: input.expires_at,
+            })
+        });
+
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(1).returning(|_| Ok(()));
+
+        execute(
+            Arc::new(repo(false)),
+            Arc::new(tokens),
+            Arc::new(mailer),
+            std::time::Duration::ZERO,
+            "email".to_string(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn should_not_resend_within_interval() {
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_find_by_user()
+            .times(1)
+            .returning(|_, _| Ok(previous_token(Duration::seconds(10))));
+
+        execute(
+            Arc::new(repo(false)),
+            Arc::new(tokens),
+            Arc::new(MockMailer::new()),
+            std::time::Duration::ZERO,
+            "email".to_string(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn should_not_resend_to_verified_user() {
+        execute(
+            Arc::new(repo(true)),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(MockMailer::new()),
+            std::time::Duration::ZERO,
+            "email".to_string(),
+        )
+        .await;
+    }
+}
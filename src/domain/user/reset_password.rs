@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use crate::repositories::{one_time_token, session, user};
+
+use super::{entities::TokenPurpose, hash_password, password_policy, secret_token};
+
+pub struct Input {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ResetPasswordError {
+    InvalidToken,
+    InvalidPassword,
+    Unknown,
+}
+
+/// Sets a new password using a token from `request_password_reset`, then signs the user out
+/// everywhere.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    params: Arc<hash_password::Params>,
+    input: Input,
+) -> Result<(), ResetPasswordError> {
+    let Input {
+        token,
+        new_password,
+    } = input;
+
+    // Checked before consuming the token so a rejected password doesn't use it up
+    if password_policy::validate(&new_password).is_err() {
+        return Err(ResetPasswordError::InvalidPassword);
+    }
+
+    let token = tokens
+        .consume(TokenPurpose::PasswordReset, secret_token::hash(&token))
+        .await;
+
+    let token = match token {
+        Ok(Some(token)) => token,
+        Ok(None) => return Err(ResetPasswordError::InvalidToken),
+        Err(err) => {
+            log::error!("Error In reset_password: {}", err);
+            return Err(ResetPasswordError::Unknown);
+        }
+    };
+
+    let hashed_password = match hash_password::spawn_execute(new_password, *params).await {
+        Ok(hash) => hash,
+        Err(_) => return Err(ResetPasswordError::InvalidPassword),
+    };
+
+    match repo
+        .update_password(token.user_id.clone(), hashed_password)
+        .await
+    {
+        Ok(()) => {}
+        Err(user::UpdatePasswordError::NotFound) | Err(user::UpdatePasswordError::InvalidId) => {
+            return Err(ResetPasswordError::InvalidToken)
+        }
+        Err(err @ user::UpdatePasswordError::Database(_)) => {
+            log::error!("Error In reset_password: {}", err);
+            return Err(ResetPasswordError::Unknown);
+        }
+    };
+
+    match sessions.revoke_all_for_user(token.user_id, None).await {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            log::error!("Error In reset_password: {}", err);
+            Err(ResetPasswordError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use crate::{
+        domain::user::entities::OneTimeToken,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository,
+            session::MockRepository as MockSessionRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    const PARAMS: hash_password::Params = hash_password::Params {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn input(new_password: &str) -> Input {
+        Input {
+            token: "token".to_string(),
+            new_password: new_password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_update_password_and_revoke_sessions() {
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_consume()
+            .times(1)
+            .withf(|purpose, hash| {
+                *purpose == TokenPurpose::PasswordReset && *hash == secret_token::hash("token")
+            })
+            .returning(|purpose, _| {
+                Ok(Some(OneTimeToken {
+                    id: "token".to_string(),
+                    user_id: "id".to_string(),
+                    purpose,
+                    created_at: Utc::now(),
+                    expires_at: Utc::now() + Duration::minutes(5),
+                }))
+            });
+
+        let mut repo = MockRepository::new();
+        repo.expect_update_password()
+            .times(1)
+            .withf(|id, password| {
+                id == "id" && hash_password::verify("new password", password) == Ok(true)
+            })
+            .returning(|_, _| Ok(()));
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all_for_user()
+            .times(1)
+            .withf(|user_id, except_id| user_id == "id" && except_id.is_none())
+            .returning(|_, _| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(sessions),
+            Arc::new(tokens),
+            Arc::new(PARAMS),
+            input("new password"),
+        )
+        .await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_token_error_for_unknown_or_used_token() {
+        let mut tokens = MockTokenRepository::new();
+        tokens.expect_consume().times(1).returning(|_, _| Ok(None));
+
+        let results = execute(
+            Arc::new(MockRepository::new()),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(tokens),
+            Arc::new(PARAMS),
+            input("new password"),
+        )
+        .await;
+
+        assert_eq!(results, Err(ResetPasswordError::InvalidToken));
+    }
+
+    #[tokio::test]
+    async fn should_not_consume_token_when_password_is_invalid() {
+        let results = execute(
+            Arc::new(MockRepository::new()),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(MockTokenRepository::new()),
+            Arc::new(PARAMS),
+            input("short"),
+        )
+        .await;
+
+        assert_eq!(results, Err(ResetPasswordError::InvalidPassword));
+    }
+}
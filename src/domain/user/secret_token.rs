@@ -0,0 +1,34 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Generates an opaque random token along with the hash that gets persisted for it, so a leaked
+/// database never reveals usable tokens.
+pub fn generate() -> (String, String) {
+    let mut bytes = [0u8; TOKEN_LENGTH];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let token = base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+    let hash = hash(&token);
+
+    (token, hash)
+}
+
+pub fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_generate_unique_tokens_matching_their_hash() {
+        let (first, first_hash) = generate();
+        let (second, _) = generate();
+
+        assert_ne!(first, second);
+        assert_eq!(hash(&first), first_hash);
+    }
+}
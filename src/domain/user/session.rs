@@ -0,0 +1,59 @@
+use std::fmt;
+
+use chrono::{Duration, Utc};
+
+use crate::repositories::session;
+
+use super::{
+    access_token::{self, AccessToken},
+    secret_token,
+};
+
+const SESSION_TTL_DAYS: i64 = 30;
+
+pub struct Tokens {
+    pub access_token: AccessToken,
+    pub refresh_token: String,
+}
+
+#[derive(Debug)]
+pub enum StartError {
+    Create(session::CreateError),
+    /// The access token could not be signed
+    Issue,
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartError::Create(err) => err.fmt(f),
+            StartError::Issue => f.write_str("could not issue access token"),
+        }
+    }
+}
+
+/// Opens a new session for the user, returning its first access and refresh tokens.
+pub async fn start(
+    repo: &dyn session::Repository,
+    keys: &access_token::Keys,
+    user_id: String,
+) -> Result<Tokens, StartError> {
+    let (refresh_token, refresh_token_hash) = secret_token::generate();
+
+    let session = repo
+        .create(session::CreateInput {
+            user_id,
+            refresh_token_hash,
+            expires_at: Utc::now() + Duration::days(SESSION_TTL_DAYS),
+        })
+        .await
+        .map_err(StartError::Create)?;
+
+    let access_token =
+        access_token::issue(keys, &session.user_id, &session.id).map_err(|()| StartError::Issue)?;
+
+    Ok(Tokens {
+        access_token,
+        refresh_token,
+    })
+}
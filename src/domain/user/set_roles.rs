@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use crate::repositories::user;
+
+use super::{admin_create_user::unique, entities::Role};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetRolesError {
+    NotFound,
+    Unknown,
+}
+
+/// Replaces the user's roles, which guards pick up on the next request.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    user_id: String,
+    roles: Vec<Role>,
+) -> Result<(), SetRolesError> {
+    let results = repo.set_roles(user_id, unique(roles)).await;
+
+    match results {
+        Ok(()) => Ok(()),
+        Err(user::SetRolesError::NotFound) | Err(user::SetRolesError::InvalidId) => {
+            Err(SetRolesError::NotFound)
+        }
+        Err(err @ user::SetRolesError::Database(_)) => {
+            log::error!("Error In set_roles: {}", err);
+            Err(SetRolesError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::user::MockRepository;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_store_roles_without_duplicates() {
+        let mut repo = MockRepository::new();
+        repo.expect_set_roles()
+            .times(1)
+            .withf(|id, roles| id == "id" && roles == &vec![Role::Admin, Role::User])
+            .returning(|_, _| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            "id".to_string(),
+            vec![Role::Admin, Role::User, Role::Admin],
+        )
+        .await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_for_unknown_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_set_roles()
+            .times(1)
+            .returning(|_, _| Err(user::SetRolesError::NotFound));
+
+        let results = execute(Arc::new(repo), "id".to_string(), vec![Role::User]).await;
+
+        assert_eq!(results, Err(SetRolesError::NotFound));
+    }
+}
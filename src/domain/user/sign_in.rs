@@ -1,70 +1,414 @@
-use crate::repositories::user;
+use crate::repositories::{
+    one_time_token, session as session_repository,
+    sign_in_attempt::{self, Subject},
+    user,
+};
+use chrono::{DateTime, Utc};
 use std::{sync::Arc, time::Duration};
 use tokio::time::sleep;
 
-use super::{entities::User, hash_password};
+use super::{
+    access_token, email_address,
+    entities::{TokenPurpose, User},
+    hash_password,
+    lockout::{self, LockoutError},
+    secret_token,
+    session::{self, Tokens},
+};
+
+/// Default minimum time taken to answer, so responses do not reveal whether an account exists.
+pub const RESPONSE_PADDING: Duration = Duration::from_millis(500);
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// Refuse to start sessions for users who haven't verified their email yet
+    pub require_verified_email: bool,
+    pub lockout: lockout::Settings,
+    /// Minimum time taken to answer sign in, and requests for emails about an account
+    pub response_padding: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            require_verified_email: false,
+            lockout: lockout::Settings::default(),
+            response_padding: RESPONSE_PADDING,
+        }
+    }
+}
 
 pub struct Input {
     pub email: String,
     pub password: String,
 }
+
+pub struct SignedIn {
+    pub user: User,
+    pub tokens: Tokens,
+}
+
+/// Proof the password matched, exchanged for a session with `verify_totp` once the user also
+/// provides a code from their second factor.
+pub struct Challenge {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub enum Output {
+    SignedIn(Box<SignedIn>),
+    SecondFactorRequired(Challenge),
+}
+
 pub enum SignInError {
-    InvalidPasswordFormat,
     Failed,
+    Unverified,
+    Disabled,
+    /// Too many failed attempts against the account or email, until the lock's cooldown ends
+    Locked,
     Unknown,
 }
 
-async fn logic(repo: Arc<dyn user::Repository>, input: Input) -> Result<User, SignInError> {
+impl From<LockoutError> for SignInError {
+    fn from(err: LockoutError) -> Self {
+        match err {
+            LockoutError::Locked => SignInError::Locked,
+            LockoutError::Unknown => SignInError::Unknown,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn logic(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session_repository::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    attempts: Arc<dyn sign_in_attempt::Repository>,
+    keys: Arc<access_token::Keys>,
+    params: Arc<hash_password::Params>,
+    settings: Arc<Settings>,
+    input: Input,
+) -> Result<Output, SignInError> {
     let Input { email, password } = input;
+    let attempts = attempts.as_ref();
+
+    // Counting per email too means guesses are limited even against emails without an account
+    let email = email_address::normalize(&email);
+    let email_subject = Subject::Email(email.clone());
+    lockout::check(attempts, email_subject.clone()).await?;
 
     let user = repo.find_one_by_email(email.clone()).await;
 
     let user = match user {
         Ok(Some(user)) => user,
-        Ok(None) => return Err(SignInError::Failed),
-        Err(user::FindOneByEmailError::Unknown) => return Err(SignInError::Unknown),
+        Ok(None) => {
+            lockout::record_failure(attempts, &settings.lockout, email_subject).await;
+            return Err(SignInError::Failed);
+        }
+        Err(err @ user::FindOneByEmailError::Database(_)) => {
+            log::error!("Error In sign_in: {}", err);
+            return Err(SignInError::Unknown);
+        }
     };
 
-    let hashed_password = match hash_password::execute(password) {
-        Ok(pass) => pass,
-        Err(_) => return Err(SignInError::InvalidPasswordFormat),
+    let account_subject = Subject::Account(user.id.clone());
+    lockout::check(attempts, account_subject.clone()).await?;
+
+    match hash_password::spawn_verify(password.clone(), user.password.clone()).await {
+        Ok(true) => {}
+        Ok(false) | Err(_) => {
+            lockout::record_failure(attempts, &settings.lockout, email_subject).await;
+            lockout::record_failure(attempts, &settings.lockout, account_subject).await;
+            return Err(SignInError::Failed);
+        }
+    };
+
+    lockout::clear(attempts, email_subject).await;
+
+    // Only revealed once the password matched, so they can't be used to probe for accounts
+    if user.disabled {
+        return Err(SignInError::Disabled);
+    }
+
+    if settings.require_verified_email && user.verified_at.is_none() {
+        return Err(SignInError::Unverified);
+    }
+
+    // Upgrade hashes made with a legacy salt or weaker parameters now that we know the plaintext
+    let hashed_password = if hash_password::needs_rehash(&user.password, &params) {
+        rehash(repo.as_ref(), &user.id, password, &params)
+            .await
+            .unwrap_or(user.password)
+    } else {
+        user.password
     };
 
-    if user.password != hashed_password {
-        return Err(SignInError::Failed);
+    if user.two_factor.is_enabled() {
+        // Counted as failed until verify_totp clears it, so guessing codes is limited by the same
+        // lockout as guessing passwords
+        lockout::record_failure(attempts, &settings.lockout, account_subject).await;
+
+        return match challenge(tokens.as_ref(), user.id).await {
+            Ok(challenge) => Ok(Output::SecondFactorRequired(challenge)),
+            Err(err) => {
+                log::error!("Error In sign_in: {}", err);
+                Err(SignInError::Unknown)
+            }
+        };
     }
 
-    Ok(User {
-        id: user.id,
-        email: user.email,
-        password: user.password,
+    lockout::clear(attempts, account_subject).await;
+
+    let tokens = match session::start(sessions.as_ref(), &keys, user.id.clone()).await {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            log::error!("Error In sign_in: {}", err);
+            return Err(SignInError::Unknown);
+        }
+    };
+
+    Ok(Output::SignedIn(Box::new(SignedIn {
+        user: User {
+            password: hashed_password,
+            ..user
+        },
+        tokens,
+    })))
+}
+
+async fn challenge(
+    tokens: &dyn one_time_token::Repository,
+    user_id: String,
+) -> Result<Challenge, one_time_token::CreateError> {
+    let (token, token_hash) = secret_token::generate();
+
+    let challenge = tokens
+        .create(one_time_token::CreateInput {
+            user_id,
+            purpose: TokenPurpose::SignInChallenge,
+            token_hash,
+            expires_at: Utc::now() + chrono::Duration::minutes(CHALLENGE_TTL_MINUTES),
+        })
+        .await?;
+
+    Ok(Challenge {
+        token,
+        expires_at: challenge.expires_at,
     })
 }
 
-pub async fn execute(repo: Arc<dyn user::Repository>, input: Input) -> Result<User, SignInError> {
-    // Adding delay so it always take 500ms to respond to prevent from seeing difference
-    let (_, results) = tokio::join!(sleep(Duration::from_millis(500)), logic(repo, input));
+async fn rehash(
+    repo: &dyn user::Repository,
+    id: &str,
+    password: String,
+    params: &hash_password::Params,
+) -> Option<String> {
+    let hashed_password = hash_password::spawn_execute(password, *params).await.ok()?;
+
+    match repo
+        .update_password(id.to_string(), hashed_password.clone())
+        .await
+    {
+        Ok(()) => Some(hashed_password),
+        Err(err) => {
+            log::error!(
+                "Error In sign_in rehashing password for user {}: {}",
+                id,
+                err
+            );
+            None
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session_repository::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    attempts: Arc<dyn sign_in_attempt::Repository>,
+    keys: Arc<access_token::Keys>,
+    params: Arc<hash_password::Params>,
+    settings: Arc<Settings>,
+    input: Input,
+) -> Result<Output, SignInError> {
+    // Adding delay so it always takes the same time to respond to prevent from seeing difference
+    let (_, results) = tokio::join!(
+        sleep(settings.response_padding),
+        logic(repo, sessions, tokens, attempts, keys, params, settings, input)
+    );
 
     results
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::{
+        domain::user::entities::{OneTimeToken, SignInAttempts, TwoFactor},
+        repositories::error::{DatabaseError, DatabaseErrorKind},
+    };
+
     use super::*;
 
+    fn sessions() -> Arc<session_repository::MockRepository> {
+        let mut sessions = session_repository::MockRepository::new();
+        sessions.expect_create().returning(|input| {
+            Ok(crate::domain::user::entities::Session {
+                id: "session".to_string(),
+                user_id: input.user_id,
+                refresh_token_hash: input.refresh_token_hash,
+                revoked: false,
+                expires_at: input.expires_at,
+            })
+        });
+
+        Arc::new(sessions)
+    }
+
+    fn attempts() -> Arc<sign_in_attempt::MockRepository> {
+        let mut attempts = sign_in_attempt::MockRepository::new();
+        attempts.expect_find().returning(|_| Ok(None));
+        attempts.expect_record_failure().returning(|_, _| {
+            Ok(SignInAttempts {
+                failures: 1,
+                locked_until: None,
+            })
+        });
+        attempts.expect_clear().returning(|_| Ok(()));
+
+        Arc::new(attempts)
+    }
+
+    /// Without padding, so tests don't wait for it
+    fn settings() -> Settings {
+        Settings {
+            response_padding: Duration::ZERO,
+            ..Settings::default()
+        }
+    }
+
+    fn keys() -> Arc<access_token::Keys> {
+        Arc::new(access_token::Keys::from_secret(
+            b"secret",
+            chrono::Duration::minutes(15),
+        ))
+    }
+
+    const PARAMS: hash_password::Params = hash_password::Params {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
     #[tokio::test]
     async fn should_return_user_if_password_match() {
         let mut repo = user::MockRepository::new();
         repo.expect_find_one_by_email().times(1).returning(|email| {
-            Ok(Some(User {
-                id: "id".to_string(),
-                email,
-                password: hash_password::execute("pass".to_string()).unwrap(),
-            }))
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(hash_password::execute("pass".to_string(), &PARAMS).unwrap())
+                    .build(),
+            ))
         });
 
         let results = execute(
             Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Ok(Output::SignedIn(output)) => {
+                assert_eq!(output.user.id, "id");
+                assert!(!output.tokens.access_token.token.is_empty());
+                assert!(!output.tokens.refresh_token.is_empty());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_rehash_legacy_password() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email().times(1).returning(|email| {
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(
+                        argon2::hash_encoded(b"pass", b"testSalt", &argon2::Config::default())
+                            .unwrap(),
+                    )
+                    .build(),
+            ))
+        });
+        repo.expect_update_password()
+            .times(1)
+            .withf(|id, password| id == "id" && !hash_password::needs_rehash(password, &PARAMS))
+            .returning(|_, _| Ok(()));
+
+        let results = execute(
+            Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Ok(Output::SignedIn(output)) => {
+                assert!(!hash_password::needs_rehash(&output.user.password, &PARAMS))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_sign_in_when_rehash_fails() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email().times(1).returning(|email| {
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(hash_password::execute("pass".to_string(), &PARAMS).unwrap())
+                    .build(),
+            ))
+        });
+        repo.expect_update_password().times(1).returning(|_, _| {
+            Err(user::UpdatePasswordError::Database(DatabaseError::new(
+                DatabaseErrorKind::Timeout,
+                "timed out",
+            )))
+        });
+
+        let results = execute(
+            Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(hash_password::Params {
+                iterations: 2,
+                ..PARAMS
+            }),
+            Arc::new(settings()),
             Input {
                 email: "email".to_string(),
                 password: "pass".to_string(),
@@ -78,6 +422,242 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn should_return_challenge_instead_of_session_when_two_factor_is_enabled() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email().times(1).returning(|email| {
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(hash_password::execute("pass".to_string(), &PARAMS).unwrap())
+                    .two_factor(TwoFactor {
+                        secret: Some("SECRET".to_string()),
+                        ..TwoFactor::default()
+                    })
+                    .build(),
+            ))
+        });
+
+        let mut tokens = one_time_token::MockRepository::new();
+        tokens
+            .expect_create()
+            .times(1)
+            .withf(|input| input.user_id == "id" && input.purpose == TokenPurpose::SignInChallenge)
+            .returning(|input| {
+                Ok(OneTimeToken {
+                    id: "challenge".to_string(),
+                    user_id: input.user_id,
+                    purpose: input.purpose,
+                    created_at: Utc::now(),
+                    expires_at: input.expires_at,
+                })
+            });
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(session_repository::MockRepository::new()),
+            Arc::new(tokens),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Ok(Output::SecondFactorRequired(challenge)) => assert!(!challenge.token.is_empty()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_unverified_error_when_verification_is_required() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email().times(1).returning(|email| {
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(hash_password::execute("pass".to_string(), &PARAMS).unwrap())
+                    .build(),
+            ))
+        });
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(session_repository::MockRepository::new()),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(Settings {
+                require_verified_email: true,
+                ..settings()
+            }),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Err(SignInError::Unverified) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_disabled_error_for_disabled_user() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email().times(1).returning(|email| {
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(hash_password::execute("pass".to_string(), &PARAMS).unwrap())
+                    .disabled()
+                    .build(),
+            ))
+        });
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(session_repository::MockRepository::new()),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Err(SignInError::Disabled) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_locked_error_while_email_is_locked() {
+        let mut attempts = sign_in_attempt::MockRepository::new();
+        attempts
+            .expect_find()
+            .times(1)
+            .withf(|subject| *subject == Subject::Email("email".to_string()))
+            .returning(|_| {
+                Ok(Some(SignInAttempts {
+                    failures: 5,
+                    locked_until: Some(Utc::now() + chrono::Duration::minutes(1)),
+                }))
+            });
+
+        let results = execute(
+            Arc::new(user::MockRepository::new()),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            Arc::new(attempts),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
+            Input {
+                email: "Email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Err(SignInError::Locked) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_count_wrong_password_against_account_and_email() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email().times(1).returning(|email| {
+            Ok(Some(
+                User::builder()
+                    .email(email)
+                    .password(hash_password::execute("pass".to_string(), &PARAMS).unwrap())
+                    .build(),
+            ))
+        });
+
+        let mut attempts = sign_in_attempt::MockRepository::new();
+        attempts.expect_find().times(2).returning(|_| Ok(None));
+        attempts
+            .expect_record_failure()
+            .times(2)
+            .withf(|subject, _| {
+                *subject == Subject::Email("email".to_string())
+                    || *subject == Subject::Account("id".to_string())
+            })
+            .returning(|_, _| {
+                Ok(SignInAttempts {
+                    failures: 1,
+                    locked_until: None,
+                })
+            });
+
+        let results = execute(
+            Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            Arc::new(attempts),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
+            Input {
+                email: "email".to_string(),
+                password: "wrong".to_string(),
+            },
+        )
+        .await;
+
+        match results {
+            Err(SignInError::Failed) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_take_at_least_the_padding_to_answer() {
+        let mut repo = user::MockRepository::new();
+        repo.expect_find_one_by_email()
+            .times(1)
+            .returning(|_| Ok(None));
+        let padding = Duration::from_millis(50);
+        let started = std::time::Instant::now();
+
+        let _ = execute(
+            Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(Settings {
+                response_padding: padding,
+                ..settings()
+            }),
+            Input {
+                email: "email".to_string(),
+                password: "pass".to_string(),
+            },
+        )
+        .await;
+
+        assert!(started.elapsed() >= padding);
+    }
+
     #[tokio::test]
     async fn should_return_failed_error_if_no_user_found() {
         let mut repo = user::MockRepository::new();
@@ -87,6 +667,12 @@ mod tests {
 
         let results = execute(
             Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
             Input {
                 email: "email".to_string(),
                 password: "pass".to_string(),
@@ -104,15 +690,19 @@ mod tests {
     async fn should_return_failed_error_if_password_doesnt_match() {
         let mut repo = user::MockRepository::new();
         repo.expect_find_one_by_email().times(1).returning(|email| {
-            Ok(Some(User {
-                id: "id".to_string(),
-                email,
-                password: "unknown".to_string(),
-            }))
+            Ok(Some(
+                User::builder().email(email).password("unknown").build(),
+            ))
         });
 
         let results = execute(
             Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
             Input {
                 email: "email".to_string(),
                 password: "pass".to_string(),
@@ -129,12 +719,21 @@ mod tests {
     #[tokio::test]
     async fn should_return_unknown_error_if_repo_return_unknown() {
         let mut repo = user::MockRepository::new();
-        repo.expect_find_one_by_email()
-            .times(1)
-            .returning(|_| Err(user::FindOneByEmailError::Unknown));
+        repo.expect_find_one_by_email().times(1).returning(|_| {
+            Err(user::FindOneByEmailError::Database(DatabaseError::new(
+                DatabaseErrorKind::Connectivity,
+                "connection refused",
+            )))
+        });
 
         let results = execute(
             Arc::new(repo),
+            sessions(),
+            Arc::new(one_time_token::MockRepository::new()),
+            attempts(),
+            keys(),
+            Arc::new(PARAMS),
+            Arc::new(settings()),
             Input {
                 email: "email".to_string(),
                 password: "pass".to_string(),
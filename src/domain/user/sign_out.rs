@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crate::repositories::session;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum SignOutError {
+    Unknown,
+}
+
+/// Revokes the session the caller is signed in with.
+pub async fn execute(
+    repo: Arc<dyn session::Repository>,
+    session_id: String,
+) -> Result<(), SignOutError> {
+    let results = repo.revoke(session_id).await;
+
+    match results {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            log::error!("Error In sign_out: {}", err);
+            Err(SignOutError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::{
+        error::{DatabaseError, DatabaseErrorKind},
+        session::MockRepository,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_revoke_session() {
+        let mut repo = MockRepository::new();
+        repo.expect_revoke()
+            .times(1)
+            .withf(|id| id == "session")
+            .returning(|_| Ok(()));
+
+        let results = execute(Arc::new(repo), "session".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_unknown_error_if_revoke_fails() {
+        let mut repo = MockRepository::new();
+        repo.expect_revoke().times(1).returning(|_| {
+            Err(session::RevokeError::Database(DatabaseError::new(
+                DatabaseErrorKind::Connectivity,
+                "unreachable",
+            )))
+        });
+
+        let results = execute(Arc::new(repo), "session".to_string()).await;
+
+        assert_eq!(results, Err(SignOutError::Unknown));
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use crate::repositories::session;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum SignOutEverywhereError {
+    Unknown,
+}
+
+/// Revokes every session belonging to the user, including the one making the request.
+pub async fn execute(
+    repo: Arc<dyn session::Repository>,
+    user_id: String,
+) -> Result<(), SignOutEverywhereError> {
+    let results = repo.revoke_all_for_user(user_id, None).await;
+
+    match results {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            log::error!("Error In sign_out_everywhere: {}", err);
+            Err(SignOutEverywhereError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::session::MockRepository;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_revoke_all_sessions_for_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_revoke_all_for_user()
+            .times(1)
+            .withf(|user_id, except_id| user_id == "user" && except_id.is_none())
+            .returning(|_, _| Ok(()));
+
+        let results = execute(Arc::new(repo), "user".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+}
@@ -0,0 +1,201 @@
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use sha1::Sha1;
+use subtle::ConstantTimeEq;
+
+const SECRET_LENGTH: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Steps either side of the current one that are still accepted, to allow for clock drift
+const ALLOWED_SKEW_STEPS: i64 = 1;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generates a random shared secret, base32 encoded as authenticator apps expect it.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_LENGTH];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    encode_base32(&bytes)
+}
+
+/// Builds the `otpauth://` URI authenticator apps scan to add the account.
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = encode_uri_component(issuer),
+        account = encode_uri_component(account),
+        secret = secret,
+        digits = DIGITS,
+        period = STEP_SECONDS,
+    )
+}
+
+/// The RFC 6238 code for a base32 secret at the given unix time.
+pub fn code_at(secret: &str, unix_time: i64) -> Option<String> {
+    let secret = decode_base32(secret)?;
+    let counter = u64::try_from(unix_time.div_euclid(STEP_SECONDS)).ok()?;
+
+    Some(format!(
+        "{:0width$}",
+        hotp(&secret, counter, DIGITS),
+        width = DIGITS as usize
+    ))
+}
+
+/// Checks a code against the step containing `unix_time` and its immediate neighbours, returning
+/// the step it belongs to so callers can refuse to accept it twice.
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    // Every step is compared, in constant time, so timing doesn't tell how close a guess was
+    let mut accepted = None;
+    for skew in -ALLOWED_SKEW_STEPS..=ALLOWED_SKEW_STEPS {
+        let unix_time = unix_time + skew * STEP_SECONDS;
+        if let Some(expected) = code_at(secret, unix_time) {
+            if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) {
+                accepted = Some(unix_time.div_euclid(STEP_SECONDS));
+            }
+        }
+    }
+
+    accepted
+}
+
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    truncated % 10u32.pow(digits)
+}
+
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+fn decode_base32(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for char in encoded.bytes().filter(|byte| !matches!(byte, b'=' | b' ')) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&candidate| candidate == char.to_ascii_uppercase())?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'@' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Secret from the RFC 6238 test vectors, "12345678901234567890" in base32
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn should_match_rfc_6238_test_vectors() {
+        let secret = decode_base32(RFC_SECRET).unwrap();
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+
+        for (unix_time, expected) in vectors {
+            assert_eq!(
+                hotp(&secret, (unix_time / STEP_SECONDS) as u64, 8),
+                expected
+            );
+        }
+
+        assert_eq!(code_at(RFC_SECRET, 59).unwrap(), "287082");
+        assert_eq!(code_at(RFC_SECRET, 1111111109).unwrap(), "081804");
+    }
+
+    #[test]
+    fn should_accept_codes_from_adjacent_steps_only() {
+        let step = 1111111109 / STEP_SECONDS;
+
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109), Some(step));
+        assert_eq!(
+            verify(RFC_SECRET, "081804", 1111111109 + STEP_SECONDS),
+            Some(step)
+        );
+        assert_eq!(
+            verify(RFC_SECRET, "081804", 1111111109 + 3 * STEP_SECONDS),
+            None
+        );
+        assert_eq!(verify(RFC_SECRET, "81804", 1111111109), None);
+        assert_eq!(verify(RFC_SECRET, "abcdef", 1111111109), None);
+    }
+
+    #[test]
+    fn should_round_trip_base32() {
+        assert_eq!(encode_base32(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(decode_base32("mzxw6ytboi======").unwrap(), b"foobar");
+        assert_eq!(
+            decode_base32(&generate_secret()).unwrap().len(),
+            SECRET_LENGTH
+        );
+    }
+
+    #[test]
+    fn should_build_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri("SECRET", "jane+test@example.com", "My App"),
+            "otpauth://totp/My%20App:jane%2Btest@example.com?secret=SECRET&issuer=My%20App&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
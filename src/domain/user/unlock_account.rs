@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use crate::repositories::{
+    sign_in_attempt::{self, Subject},
+    user,
+};
+
+use super::email_address;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum UnlockAccountError {
+    NotFound,
+    Unknown,
+}
+
+/// Lifts a lockout before its cooldown ends, forgetting failures against both the account and
+/// its email.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    attempts: Arc<dyn sign_in_attempt::Repository>,
+    user_id: String,
+) -> Result<(), UnlockAccountError> {
+    let user = match repo.find_by_id(user_id).await {
+        Ok(user) => user,
+        Err(user::FindByIdError::NotFound) | Err(user::FindByIdError::InvalidId) => {
+            return Err(UnlockAccountError::NotFound)
+        }
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In unlock_account: {}", err);
+            return Err(UnlockAccountError::Unknown);
+        }
+    };
+
+    for subject in [
+        Subject::Account(user.id),
+        Subject::Email(email_address::normalize(&user.email)),
+    ] {
+        if let Err(err) = attempts.clear(subject).await {
+            log::error!("Error In unlock_account: {}", err);
+            return Err(UnlockAccountError::Unknown);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        domain::user::entities::User,
+        repositories::{
+            sign_in_attempt::MockRepository as MockAttemptRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_clear_account_and_email_failures() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id().times(1).returning(|id| {
+            Ok(User::builder()
+                .id(id)
+                .email("Email@example.com")
+                .password("hash")
+                .build())
+        });
+
+        let mut attempts = MockAttemptRepository::new();
+        attempts
+            .expect_clear()
+            .times(1)
+            .withf(|subject| *subject == Subject::Account("id".to_string()))
+            .returning(|_| Ok(()));
+        attempts
+            .expect_clear()
+            .times(1)
+            .withf(|subject| *subject == Subject::Email("email@example.com".to_string()))
+            .returning(|_| Ok(()));
+
+        let results = execute(Arc::new(repo), Arc::new(attempts), "id".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_for_unknown_user() {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id()
+            .times(1)
+            .returning(|_| Err(user::FindByIdError::NotFound));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockAttemptRepository::new()),
+            "id".to_string(),
+        )
+        .await;
+
+        assert_eq!(results, Err(UnlockAccountError::NotFound));
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use crate::repositories::{one_time_token, user};
+
+use super::{entities::TokenPurpose, secret_token};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum VerifyEmailError {
+    InvalidToken,
+    Unknown,
+}
+
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    token: String,
+) -> Result<(), VerifyEmailError> {
+    let token = tokens
+        .consume(TokenPurpose::EmailVerification, secret_token::hash(&token))
+        .await;
+
+    let token = match token {
+        Ok(Some(token)) => token,
+        Ok(None) => return Err(VerifyEmailError::InvalidToken),
+        Err(err) => {
+            log::error!("Error In verify_email: {}", err);
+            return Err(VerifyEmailError::Unknown);
+        }
+    };
+
+    match repo.mark_verified(token.user_id).await {
+        Ok(()) => Ok(()),
+        Err(user::MarkVerifiedError::NotFound) | Err(user::MarkVerifiedError::InvalidId) => {
+            Err(VerifyEmailError::InvalidToken)
+        }
+        Err(err @ user::MarkVerifiedError::Database(_)) => {
+            log::error!("Error In verify_email: {}", err);
+            Err(VerifyEmailError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use crate::{
+        domain::user::entities::OneTimeToken,
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_mark_user_verified() {
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_consume()
+            .times(1)
+            .withf(|purpose, hash| {
+                *purpose == TokenPurpose::EmailVerification && *hash == secret_token::hash("token")
+            })
+            .returning(|purpose, _| {
+                Ok(Some(OneTimeToken {
+                    id: "token".to_string(),
+                    user_id: "id".to_string(),
+                    purpose,
+                    created_at: Utc::now(),
+                    expires_at: Utc::now() + Duration::hours(1),
+                }))
+            });
+
+        let mut repo = MockRepository::new();
+        repo.expect_mark_verified()
+            .times(1)
+            .withf(|id| id == "id")
+            .returning(|_| Ok(()));
+
+        let results = execute(Arc::new(repo), Arc::new(tokens), "token".to_string()).await;
+
+        assert_eq!(results, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_token_error_for_unknown_token() {
+        let mut tokens = MockTokenRepository::new();
+        tokens.expect_consume().times(1).returning(|_, _| Ok(None));
+
+        let results = execute(
+            Arc::new(MockRepository::new()),
+            Arc::new(tokens),
+            "token".to_string(),
+        )
+        .await;
+
+        assert_eq!(results, Err(VerifyEmailError::InvalidToken));
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::Arc;
+
+use crate::{
+    domain::clock::Clock,
+    repositories::{
+        one_time_token, session as session_repository,
+        sign_in_attempt::{self, Subject},
+        user,
+    },
+};
+
+use super::{
+    access_token,
+    entities::{TokenPurpose, User},
+    lockout, recovery_codes, secret_token, session,
+    sign_in::SignedIn,
+    totp,
+};
+
+pub struct Input {
+    pub challenge: String,
+    /// Either the current TOTP code or one of the unused recovery codes
+    pub code: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum VerifyTotpError {
+    InvalidChallenge,
+    InvalidCode,
+    Unknown,
+}
+
+/// Completes a sign-in that `sign_in` answered with a challenge.
+///
+/// The challenge is used up by every attempt, so a wrong code means starting over with the
+/// password and codes can't be guessed without it. `sign_in` counts the attempt as failed until
+/// it is completed here.
+pub async fn execute(
+    repo: Arc<dyn user::Repository>,
+    sessions: Arc<dyn session_repository::Repository>,
+    tokens: Arc<dyn one_time_token::Repository>,
+    attempts: Arc<dyn sign_in_attempt::Repository>,
+    keys: Arc<access_token::Keys>,
+    clock: Arc<dyn Clock>,
+    input: Input,
+) -> Result<SignedIn, VerifyTotpError> {
+    let Input { challenge, code } = input;
+
+    let challenge = tokens
+        .consume(
+            TokenPurpose::SignInChallenge,
+            secret_token::hash(&challenge),
+        )
+        .await;
+
+    let challenge = match challenge {
+        Ok(Some(challenge)) => challenge,
+        Ok(None) => return Err(VerifyTotpError::InvalidChallenge),
+        Err(err) => {
+            log::error!("Error In verify_totp: {}", err);
+            return Err(VerifyTotpError::Unknown);
+        }
+    };
+
+    let user = match repo.find_by_id(challenge.user_id).await {
+        Ok(user) if user.disabled => return Err(VerifyTotpError::InvalidChallenge),
+        Ok(user) => user,
+        Err(user::FindByIdError::NotFound) | Err(user::FindByIdError::InvalidId) => {
+            return Err(VerifyTotpError::InvalidChallenge)
+        }
+        Err(err @ user::FindByIdError::Database(_)) => {
+            log::error!("Error In verify_totp: {}", err);
+            return Err(VerifyTotpError::Unknown);
+        }
+    };
+
+    check_code(repo.as_ref(), clock.as_ref(), &user, &code).await?;
+
+    lockout::clear(attempts.as_ref(), Subject::Account(user.id.clone())).await;
+
+    let tokens = match session::start(sessions.as_ref(), &keys, user.id.clone()).await {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            log::error!("Error In verify_totp: {}", err);
+            return Err(VerifyTotpError::Unknown);
+        }
+    };
+
+    Ok(SignedIn { user, tokens })
+}
+
+async fn check_code(
+    repo: &dyn user::Repository,
+    clock: &dyn Clock,
+    user: &User,
+    code: &str,
+) -> Result<(), VerifyTotpError> {
+    let secret = match &user.two_factor.secret {
+        Some(secret) => secret,
+        None => return Err(VerifyTotpError::InvalidChallenge),
+    };
+
+    if let Some(step) = totp::verify(secret, code, clock.now().timestamp()) {
+        return match repo.consume_totp_step(user.id.clone(), step).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(VerifyTotpError::InvalidCode),
+            Err(user::ConsumeTotpStepError::InvalidId) => Err(VerifyTotpError::InvalidChallenge),
+            Err(err @ user::ConsumeTotpStepError::Database(_)) => {
+                log::error!("Error In verify_totp: {}", err);
+                Err(VerifyTotpError::Unknown)
+            }
+        };
+    }
+
+    let consumed = repo
+        .consume_recovery_code(user.id.clone(), recovery_codes::hash(code))
+        .await;
+
+    match consumed {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(VerifyTotpError::InvalidCode),
+        Err(user::ConsumeRecoveryCodeError::InvalidId) => Err(VerifyTotpError::InvalidChallenge),
+        Err(err @ user::ConsumeRecoveryCodeError::Database(_)) => {
+            log::error!("Error In verify_totp: {}", err);
+            Err(VerifyTotpError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::{
+        domain::{
+            clock::FixedClock,
+            user::entities::{OneTimeToken, Session, TwoFactor},
+        },
+        repositories::{
+            one_time_token::MockRepository as MockTokenRepository,
+            session::MockRepository as MockSessionRepository,
+            sign_in_attempt::MockRepository as MockAttemptRepository, user::MockRepository,
+        },
+    };
+
+    use super::*;
+
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const NOW: i64 = 1111111109;
+
+    fn clock() -> Arc<FixedClock> {
+        Arc::new(FixedClock(Utc.timestamp(NOW, 0)))
+    }
+
+    fn keys() -> Arc<access_token::Keys> {
+        Arc::new(access_token::Keys::from_secret(
+            b"secret",
+            Duration::minutes(15),
+        ))
+    }
+
+    fn tokens() -> Arc<MockTokenRepository> {
+        let mut tokens = MockTokenRepository::new();
+        tokens
+            .expect_consume()
+            .times(1)
+            .withf(|purpose, hash| {
+                *purpose == TokenPurpose::SignInChallenge
+                    && *hash == secret_token::hash("challenge")
+            })
+            .returning(|purpose, _| {
+                Ok(Some(OneTimeToken {
+                    id: "challenge".to_string(),
+                    user_id: "id".to_string(),
+                    purpose,
+                    created_at: Utc::now(),
+                    expires_at: Utc::now() + Duration::minutes(5),
+                }))
+            });
+
+        Arc::new(tokens)
+    }
+
+    fn sessions() -> Arc<MockSessionRepository> {
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_create().times(1).returning(|input| {
+            Ok(Session {
+                id: "session".to_string(),
+                user_id: input.user_id,
+                refresh_token_hash: input.refresh_token_hash,
+                revoked: false,
+                expires_at: input.expires_at,
+            })
+        });
+
+        Arc::new(sessions)
+    }
+
+    fn attempts() -> Arc<MockAttemptRepository> {
+        let mut attempts = MockAttemptRepository::new();
+        attempts
+            .expect_clear()
+            .times(1)
+            .withf(|subject| *subject == Subject::Account("id".to_string()))
+            .returning(|_| Ok(()));
+
+        Arc::new(attempts)
+    }
+
+    fn repo() -> MockRepository {
+        let mut repo = MockRepository::new();
+        repo.expect_find_by_id().times(1).returning(|id| {
+            Ok(User::builder()
+                .id(id)
+                .password("hash")
+                .two_factor(TwoFactor {
+                    secret: Some(SECRET.to_string()),
+                    recovery_code_hashes: vec![recovery_codes::hash("abcde-fghij")],
+                    ..TwoFactor::default()
+                })
+                .build())
+        });
+
+        repo
+    }
+
+    fn input(code: &str) -> Input {
+        Input {
+            challenge: "challenge".to_string(),
+            code: code.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_start_session_for_valid_code() {
+        let mut repo = repo();
+        repo.expect_consume_totp_step()
+            .times(1)
+            .withf(|id, step| id == "id" && *step == NOW / 30)
+            .returning(|_, _| Ok(true));
+
+        let results = execute(
+            Arc::new(repo),
+            sessions(),
+            tokens(),
+            attempts(),
+            keys(),
+            clock(),
+            input(&totp::code_at(SECRET, NOW).unwrap()),
+        )
+        .await;
+
+        match results {
+            Ok(signed_in) => {
+                assert_eq!(signed_in.user.id, "id");
+                assert!(!signed_in.tokens.refresh_token.is_empty());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_code_error_for_reused_code() {
+        let mut repo = repo();
+        repo.expect_consume_totp_step()
+            .times(1)
+            .returning(|_, _| Ok(false));
+        repo.expect_consume_recovery_code().never();
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            tokens(),
+            Arc::new(MockAttemptRepository::new()),
+            keys(),
+            clock(),
+            input(&totp::code_at(SECRET, NOW).unwrap()),
+        )
+        .await;
+
+        assert_eq!(results.err(), Some(VerifyTotpError::InvalidCode));
+    }
+
+    #[tokio::test]
+    async fn should_start_session_for_unused_recovery_code() {
+        let mut repo = repo();
+        repo.expect_consume_recovery_code()
+            .times(1)
+            .withf(|id, hash| id == "id" && *hash == recovery_codes::hash("abcde-fghij"))
+            .returning(|_, _| Ok(true));
+
+        let results = execute(
+            Arc::new(repo),
+            sessions(),
+            tokens(),
+            attempts(),
+            keys(),
+            clock(),
+            input("ABCDEFGHIJ"),
+        )
+        .await;
+
+        assert!(results.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_code_error_for_wrong_code() {
+        let mut repo = repo();
+        repo.expect_consume_recovery_code()
+            .times(1)
+            .returning(|_, _| Ok(false));
+
+        let results = execute(
+            Arc::new(repo),
+            Arc::new(MockSessionRepository::new()),
+            tokens(),
+            Arc::new(MockAttemptRepository::new()),
+            keys(),
+            clock(),
+            input(&totp::code_at(SECRET, NOW - 300).unwrap()),
+        )
+        .await;
+
+        assert_eq!(results.err(), Some(VerifyTotpError::InvalidCode));
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_challenge_error_for_used_challenge() {
+        let mut tokens = MockTokenRepository::new();
+        tokens.expect_consume().times(1).returning(|_, _| Ok(None));
+
+        let results = execute(
+            Arc::new(MockRepository::new()),
+            Arc::new(MockSessionRepository::new()),
+            Arc::new(tokens),
+            Arc::new(MockAttemptRepository::new()),
+            keys(),
+            clock(),
+            input("123456"),
+        )
+        .await;
+
+        assert_eq!(results.err(), Some(VerifyTotpError::InvalidChallenge));
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::fs;
+
+use super::{Email, Mailer, SendError};
+
+/// Writes every email to its own file in a directory instead of delivering it, so flows that send
+/// mail can be exercised without an SMTP server.
+pub struct FileMailer {
+    directory: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), SendError> {
+        let now = Utc::now();
+        let path = self.directory.join(format!(
+            "{}-{}.eml",
+            now.format("%Y%m%dT%H%M%S%.6f"),
+            rand::random::<u32>()
+        ));
+
+        let contents = format!(
+            "Date: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            now.to_rfc2822(),
+            email.to,
+            email.subject,
+            email.body
+        );
+
+        if let Err(err) = fs::create_dir_all(&self.directory).await {
+            log::error!("Error creating mail directory: {}", err);
+            return Err(SendError::Unknown);
+        }
+
+        match fs::write(&path, contents).await {
+            Ok(()) => {
+                log::info!("Email to {} written to {}", email.to, path.display());
+                Ok(())
+            }
+            Err(err) => {
+                log::error!("Error writing email: {}", err);
+                Err(SendError::Unknown)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_write_email_to_directory() {
+        let directory = std::env::temp_dir().join(format!("mailer-{}", rand::random::<u64>()));
+        let mailer = FileMailer::new(&directory);
+
+        let results = mailer
+            .send(Email {
+                to: "user@example.com".to_string(),
+                subject: "Subject".to_string(),
+                body: "Body".to_string(),
+            })
+            .await;
+        assert!(results.is_ok());
+
+        let mut entries = std::fs::read_dir(&directory).unwrap();
+        let contents = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+        assert!(contents.contains("To: user@example.com\nSubject: Subject\n\nBody\n"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
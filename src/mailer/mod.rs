@@ -0,0 +1,25 @@
+pub mod file;
+
+use async_trait::async_trait;
+
+#[cfg(test)]
+use mockall::*;
+
+pub use file::FileMailer;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub enum SendError {
+    Unknown,
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), SendError>;
+}
@@ -1,19 +1,63 @@
 use std::sync::Arc;
 
+use domain::{clock::SystemClock, user::access_token};
+
 mod api;
+mod config;
 mod domain;
+mod mailer;
 mod repositories;
 
 #[tokio::main]
 async fn main() {
-    let db = repositories::connect_to_database()
-        .await
-        .expect("Error connecting to mongo");
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let config =
+        config::Config::load().unwrap_or_else(|err| panic!("Invalid configuration: {}", err));
+
+    let repositories = match config.storage {
+        #[cfg(feature = "mongo")]
+        config::Storage::Mongo(settings) => repositories::Repositories::mongo(
+            repositories::connect_to_database(&settings)
+                .await
+                .expect("Error connecting to mongo"),
+            &settings.collections,
+        ),
+        config::Storage::Memory => {
+            log::warn!("Using in-memory storage, nothing is kept once the server stops");
+            repositories::Repositories::in_memory()
+        }
+        #[cfg(feature = "sql")]
+        config::Storage::Sql { url } => repositories::Repositories::sql(
+            repositories::connect_to_sql_database(&url)
+                .await
+                .expect("Error connecting to SQL database"),
+        ),
+    };
+    let mailer = Arc::new(mailer::FileMailer::new(config.mail_directory));
+    let keys = Arc::new(access_token::Keys::from_secret(
+        config.jwt_secret.as_bytes(),
+        config.access_token_ttl,
+    ));
 
-    let repository = Arc::new(repositories::user::MongoRepository::new(db));
+    let rate_limit_store = Arc::new(api::rate_limit::MemoryStore::new());
+    rate_limit_store.prune_every(api::rate_limit::PRUNE_INTERVAL);
 
-    println!("Playground: http://localhost:8000");
-    let routes = api::make_routes(repository);
+    log::info!("Playground: http://{}/playground", config.bind_address);
+    let routes = api::make_routes(
+        api::Services {
+            repositories,
+            mailer,
+            keys,
+            password_hashing: Arc::new(config.password_hashing),
+            sign_in_settings: Arc::new(config.sign_in),
+            clock: Arc::new(SystemClock),
+        },
+        Arc::new(api::rate_limit::RateLimiter::new(
+            rate_limit_store,
+            config.rate_limit,
+        )),
+    );
 
-    warp::serve(routes).run(([0, 0, 0, 0], 8000)).await;
+    warp::serve(routes).run(config.bind_address).await;
 }
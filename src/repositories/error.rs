@@ -0,0 +1,174 @@
+#[cfg(any(feature = "mongo", feature = "sql"))]
+use std::io;
+use std::{error::Error, fmt};
+
+#[cfg(feature = "mongo")]
+use mongodb::error::{ErrorKind, WriteFailure};
+
+#[cfg(feature = "mongo")]
+const DUPLICATE_KEY_CODE: i32 = 11000;
+#[cfg(feature = "mongo")]
+const MAX_TIME_EXPIRED_CODE: i32 = 50;
+
+/// Codes PostgreSQL and SQLite report when a unique index or primary key rejects a write
+#[cfg(feature = "sql")]
+const SQL_UNIQUE_VIOLATION_CODES: [&str; 3] = ["23505", "2067", "1555"];
+
+/// Broad category of a database failure, so callers can react to the ones they expect.
+#[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DatabaseErrorKind {
+    /// A unique index rejected the write
+    DuplicateKey,
+    Timeout,
+    /// The database could not be reached
+    Connectivity,
+    /// A document did not match the shape the repository expects
+    Serialization,
+    Other,
+}
+
+/// A database failure along with the driver error that caused it.
+#[derive(Debug)]
+pub struct DatabaseError {
+    kind: DatabaseErrorKind,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+// Only a database driver ever builds or inspects one
+#[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+impl DatabaseError {
+    pub fn new(kind: DatabaseErrorKind, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            kind,
+            source: source.into(),
+        }
+    }
+
+    pub fn kind(&self) -> DatabaseErrorKind {
+        self.kind
+    }
+
+    /// Returned by the Mongo repositories when told to fail, without touching the database.
+    #[cfg(feature = "mongo")]
+    pub(super) fn simulated() -> Self {
+        Self::new(DatabaseErrorKind::Other, "simulated failure")
+    }
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            DatabaseErrorKind::DuplicateKey => "duplicate key",
+            DatabaseErrorKind::Timeout => "timed out",
+            DatabaseErrorKind::Connectivity => "could not reach database",
+            DatabaseErrorKind::Serialization => "unexpected document shape",
+            DatabaseErrorKind::Other => "database error",
+        };
+
+        write!(f, "{}: {}", kind, self.source)
+    }
+}
+
+impl Error for DatabaseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+#[cfg(feature = "mongo")]
+impl From<mongodb::error::Error> for DatabaseError {
+    fn from(err: mongodb::error::Error) -> Self {
+        let kind = match err.kind.as_ref() {
+            ErrorKind::Write(WriteFailure::WriteError(write_error))
+                if write_error.code == DUPLICATE_KEY_CODE =>
+            {
+                DatabaseErrorKind::DuplicateKey
+            }
+            ErrorKind::BulkWrite(failure)
+                if failure
+                    .write_errors
+                    .iter()
+                    .flatten()
+                    .any(|write_error| write_error.code == DUPLICATE_KEY_CODE) =>
+            {
+                DatabaseErrorKind::DuplicateKey
+            }
+            ErrorKind::Command(command_error) if command_error.code == DUPLICATE_KEY_CODE => {
+                DatabaseErrorKind::DuplicateKey
+            }
+            ErrorKind::Command(command_error) if command_error.code == MAX_TIME_EXPIRED_CODE => {
+                DatabaseErrorKind::Timeout
+            }
+            ErrorKind::Io(io_error) if io_error.kind() == io::ErrorKind::TimedOut => {
+                DatabaseErrorKind::Timeout
+            }
+            ErrorKind::Io(_)
+            | ErrorKind::ServerSelection { .. }
+            | ErrorKind::DnsResolve { .. }
+            | ErrorKind::ConnectionPoolCleared { .. } => DatabaseErrorKind::Connectivity,
+            ErrorKind::BsonDeserialization(_) | ErrorKind::BsonSerialization(_) => {
+                DatabaseErrorKind::Serialization
+            }
+            _ => DatabaseErrorKind::Other,
+        };
+
+        Self::new(kind, err)
+    }
+}
+
+#[cfg(feature = "sql")]
+impl From<sqlx::Error> for DatabaseError {
+    fn from(err: sqlx::Error) -> Self {
+        let kind = match &err {
+            sqlx::Error::Database(database_error)
+                if database_error
+                    .code()
+                    .is_some_and(|code| SQL_UNIQUE_VIOLATION_CODES.contains(&code.as_ref())) =>
+            {
+                DatabaseErrorKind::DuplicateKey
+            }
+            sqlx::Error::PoolTimedOut => DatabaseErrorKind::Timeout,
+            sqlx::Error::Io(io_error) if io_error.kind() == io::ErrorKind::TimedOut => {
+                DatabaseErrorKind::Timeout
+            }
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolClosed => {
+                DatabaseErrorKind::Connectivity
+            }
+            sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::Decode(_) => DatabaseErrorKind::Serialization,
+            _ => DatabaseErrorKind::Other,
+        };
+
+        Self::new(kind, err)
+    }
+}
+
+#[cfg(all(test, feature = "mongo"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_classify_io_timeouts() {
+        let err = DatabaseError::from(mongodb::error::Error::from(io::ErrorKind::TimedOut));
+
+        assert_eq!(err.kind(), DatabaseErrorKind::Timeout);
+    }
+
+    #[test]
+    fn should_classify_other_io_failures_as_connectivity() {
+        let err = DatabaseError::from(mongodb::error::Error::from(
+            io::ErrorKind::ConnectionRefused,
+        ));
+
+        assert_eq!(err.kind(), DatabaseErrorKind::Connectivity);
+    }
+
+    #[test]
+    fn should_keep_driver_error_as_source() {
+        let err = DatabaseError::from(mongodb::error::Error::from(io::ErrorKind::TimedOut));
+
+        assert!(err.source().unwrap().is::<mongodb::error::Error>());
+    }
+}
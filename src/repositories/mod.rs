@@ -1,26 +1,252 @@
+use std::sync::Arc;
+
+#[cfg(feature = "mongo")]
 use mongodb::{options::ClientOptions, Client, Database};
+#[cfg(feature = "sql")]
+use sqlx::{any::AnyPoolOptions, AnyPool};
 
+pub mod error;
+pub mod one_time_token;
+pub mod session;
+pub mod sign_in_attempt;
+mod support;
 pub mod user;
 
-pub async fn connect_to_database() -> mongodb::error::Result<Database> {
-    println!("Connecting to Mongo");
+/// Where to find MongoDB and which collection keeps each kind of record.
+#[cfg(feature = "mongo")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MongoSettings {
+    /// Connection string, naming the database to use in its path
+    pub uri: String,
+    /// Reported to the server, and used as the database name when `uri` has none
+    pub app_name: String,
+    pub collections: Collections,
+}
 
-    let app_name = "authenticationService";
+#[cfg(feature = "mongo")]
+impl Default for MongoSettings {
+    fn default() -> Self {
+        Self {
+            uri: "mongodb://localhost:27017/authenticationService".to_string(),
+            app_name: "authenticationService".to_string(),
+            collections: Collections::default(),
+        }
+    }
+}
 
-    let mut client_options =
-        ClientOptions::parse("mongodb://localhost:27017/authenticationService?retryWrites=true")
-            .await?;
-    // Manually set an option
-    client_options.app_name = Some(app_name.to_string());
+#[cfg(feature = "mongo")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Collections {
+    pub users: String,
+    pub sessions: String,
+    pub one_time_tokens: String,
+    pub sign_in_attempts: String,
+}
+
+#[cfg(feature = "mongo")]
+impl Default for Collections {
+    fn default() -> Self {
+        Self {
+            users: "users".to_string(),
+            sessions: "sessions".to_string(),
+            one_time_tokens: "one_time_tokens".to_string(),
+            sign_in_attempts: "sign_in_attempts".to_string(),
+        }
+    }
+}
+
+/// One of each repository, all kept in the same store.
+pub struct Repositories {
+    pub users: Arc<dyn user::Repository>,
+    pub sessions: Arc<dyn session::Repository>,
+    pub one_time_tokens: Arc<dyn one_time_token::Repository>,
+    pub sign_in_attempts: Arc<dyn sign_in_attempt::Repository>,
+}
+
+impl Repositories {
+    /// The driver pools connections behind `db`, so every repository shares it without locking.
+    #[cfg(feature = "mongo")]
+    pub fn mongo(db: Database, collections: &Collections) -> Self {
+        Self {
+            users: Arc::new(user::MongoRepository::new(db.clone(), &collections.users)),
+            sessions: Arc::new(session::MongoRepository::new(
+                db.clone(),
+                &collections.sessions,
+            )),
+            one_time_tokens: Arc::new(one_time_token::MongoRepository::new(
+                db.clone(),
+                &collections.one_time_tokens,
+            )),
+            sign_in_attempts: Arc::new(sign_in_attempt::MongoRepository::new(
+                db,
+                &collections.sign_in_attempts,
+            )),
+        }
+    }
+
+    #[cfg(feature = "sql")]
+    pub fn sql(pool: AnyPool) -> Self {
+        Self {
+            users: Arc::new(user::SqlRepository::new(pool.clone())),
+            sessions: Arc::new(session::SqlRepository::new(pool.clone())),
+            one_time_tokens: Arc::new(one_time_token::SqlRepository::new(pool.clone())),
+            sign_in_attempts: Arc::new(sign_in_attempt::SqlRepository::new(pool)),
+        }
+    }
+
+    /// Repositories that keep everything in this process, for running without a database.
+    pub fn in_memory() -> Self {
+        Self {
+            users: Arc::new(user::InMemoryRepository::new()),
+            sessions: Arc::new(session::InMemoryRepository::new()),
+            one_time_tokens: Arc::new(one_time_token::InMemoryRepository::new()),
+            sign_in_attempts: Arc::new(sign_in_attempt::InMemoryRepository::new()),
+        }
+    }
+}
+
+#[cfg(feature = "mongo")]
+pub async fn connect_to_database(settings: &MongoSettings) -> mongodb::error::Result<Database> {
+    log::info!("Connecting to Mongo");
+
+    let mut client_options = ClientOptions::parse(&settings.uri).await?;
+    client_options.app_name = Some(settings.app_name.clone());
 
     let client = Client::with_options(client_options)?;
 
-    let db = client.database(app_name);
+    let db = client
+        .default_database()
+        .unwrap_or_else(|| client.database(&settings.app_name));
+
+    ensure_indexes(&db, &settings.collections).await?;
 
     for collection in db.list_collection_names(None).await? {
-        println!("{}", collection);
+        log::debug!("Found collection {}", collection);
     }
-    println!("Connected successfully.");
+    log::info!("Connected successfully");
 
     Ok(db)
 }
+
+/// Creates the indexes every Mongo repository relies on. Does nothing for indexes that already
+/// exist.
+#[cfg(feature = "mongo")]
+pub async fn ensure_indexes(
+    db: &Database,
+    collections: &Collections,
+) -> mongodb::error::Result<()> {
+    user::adapter::ensure_indexes(db, &collections.users).await?;
+    session::adapter::ensure_indexes(db, &collections.sessions).await?;
+    one_time_token::adapter::ensure_indexes(db, &collections.one_time_tokens).await?;
+    sign_in_attempt::adapter::ensure_indexes(db, &collections.sign_in_attempts).await?;
+
+    Ok(())
+}
+
+/// Connects to the SQLite or PostgreSQL database at `url`, bringing its tables up to date.
+#[cfg(feature = "sql")]
+pub async fn connect_to_sql_database(url: &str) -> Result<AnyPool, sqlx::Error> {
+    log::info!("Connecting to SQL database");
+
+    let pool = AnyPoolOptions::new().connect(url).await?;
+
+    sqlx::migrate!().run(&pool).await?;
+
+    log::info!("Connected successfully");
+
+    Ok(pool)
+}
+
+#[cfg(all(test, feature = "mongo"))]
+mod tests {
+    use std::time::Instant;
+
+    use chrono::Duration;
+    use futures_util::future::join_all;
+    use mongodb::Client;
+
+    use crate::domain::user::{access_token, entities::Role, hash_password, sign_in};
+
+    use super::*;
+
+    const SIGN_INS: usize = 200;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore = "needs MongoDB on localhost:27017"]
+    async fn should_not_queue_concurrent_sign_ins() {
+        let client = Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let db = client.database("authenticationServiceLoadTest");
+        db.drop(None).await.unwrap();
+        let collections = Collections::default();
+        ensure_indexes(&db, &collections).await.unwrap();
+        let repositories = Repositories::mongo(db.clone(), &collections);
+
+        // Cheap hashes, so the time goes on the database rather than Argon2
+        let params = Arc::new(hash_password::Params {
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        });
+        let password = hash_password::execute("password".to_string(), &params).unwrap();
+        for index in 0..SIGN_INS {
+            repositories
+                .users
+                .create(user::CreateInput {
+                    email: format!("user{}@example.com", index),
+                    password: password.clone(),
+                    roles: vec![Role::User],
+                })
+                .await
+                .unwrap();
+        }
+        let keys = Arc::new(access_token::Keys::from_secret(
+            b"secret",
+            Duration::minutes(15),
+        ));
+        // No padding, so the time measured is the time spent signing in
+        let settings = Arc::new(sign_in::Settings {
+            response_padding: std::time::Duration::ZERO,
+            ..sign_in::Settings::default()
+        });
+        let sign_in = |index: usize| {
+            sign_in::execute(
+                repositories.users.clone(),
+                repositories.sessions.clone(),
+                repositories.one_time_tokens.clone(),
+                repositories.sign_in_attempts.clone(),
+                keys.clone(),
+                params.clone(),
+                settings.clone(),
+                sign_in::Input {
+                    email: format!("user{}@example.com", index),
+                    password: "password".to_string(),
+                },
+            )
+        };
+
+        let started = Instant::now();
+        for index in 0..SIGN_INS {
+            assert!(sign_in(index).await.is_ok());
+        }
+        let sequential = started.elapsed();
+
+        let started = Instant::now();
+        let results = join_all((0..SIGN_INS).map(|index| tokio::spawn(sign_in(index)))).await;
+        let concurrent = started.elapsed();
+        db.drop(None).await.unwrap();
+
+        assert!(results
+            .into_iter()
+            .all(|result| matches!(result, Ok(Ok(_)))));
+        // Sign-ins that run side by side finish well before the same ones one after another,
+        // while ones that queue on a shared lock take about as long
+        assert!(
+            concurrent < sequential / 2,
+            "concurrent sign-ins took {:?}, sequential ones {:?}",
+            concurrent,
+            sequential
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::IndexOptions,
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::user::entities::{OneTimeToken, TokenPurpose},
+    repositories::error::DatabaseError,
+};
+
+use super::{purpose_name, ConsumeError, CreateError, CreateInput, FindByUserError, Repository};
+
+pub struct MongoRepository {
+    collection: Collection<OneTimeTokenDocument>,
+    error: bool,
+}
+
+impl MongoRepository {
+    pub fn new(db: Database, collection: &str) -> Self {
+        Self {
+            error: false,
+            collection: db.collection(collection),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct OneTimeTokenDocument {
+    _id: ObjectId,
+    user_id: String,
+    purpose: String,
+    token_hash: String,
+    created_at: DateTime,
+    expires_at: DateTime,
+}
+
+impl OneTimeTokenDocument {
+    fn into_token(self, purpose: TokenPurpose) -> OneTimeToken {
+        OneTimeToken {
+            id: self._id.to_hex(),
+            user_id: self.user_id,
+            purpose,
+            created_at: Utc.timestamp_millis(self.created_at.timestamp_millis()),
+            expires_at: Utc.timestamp_millis(self.expires_at.timestamp_millis()),
+        }
+    }
+}
+
+/// Creates the indexes behind looking tokens up by their hash and by their user. Does nothing for
+/// indexes that already exist.
+pub async fn ensure_indexes(database: &Database, collection: &str) -> mongodb::error::Result<()> {
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "purpose": 1, "token_hash": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("purpose_token_hash_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "user_id": 1, "purpose": 1 })
+            .build(),
+    ];
+
+    database
+        .collection::<Document>(collection)
+        .create_indexes(indexes, None)
+        .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Repository for MongoRepository {
+    async fn create(&self, input: CreateInput) -> Result<OneTimeToken, CreateError> {
+        if self.error {
+            return Err(CreateError::Database(DatabaseError::simulated()));
+        }
+
+        let now = SystemTime::now();
+
+        let deleted = self
+            .collection
+            .delete_many(
+                doc! { "user_id": &input.user_id, "purpose": purpose_name(input.purpose) },
+                None,
+            )
+            .await;
+
+        if let Err(err) = deleted {
+            return Err(CreateError::Database(err.into()));
+        }
+
+        let new_doc = OneTimeTokenDocument {
+            _id: ObjectId::new(),
+            user_id: input.user_id,
+            purpose: purpose_name(input.purpose).to_string(),
+            token_hash: input.token_hash,
+            created_at: DateTime::from_system_time(now),
+            expires_at: DateTime::from_millis(input.expires_at.timestamp_millis()),
+        };
+
+        let results = self.collection.insert_one(&new_doc, None).await;
+
+        match results {
+            Ok(_) => Ok(new_doc.into_token(input.purpose)),
+            Err(err) => Err(CreateError::Database(err.into())),
+        }
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: String,
+        purpose: TokenPurpose,
+    ) -> Result<Option<OneTimeToken>, FindByUserError> {
+        if self.error {
+            return Err(FindByUserError::Database(DatabaseError::simulated()));
+        }
+
+        let results = self
+            .collection
+            .find_one(
+                doc! { "user_id": user_id, "purpose": purpose_name(purpose) },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(doc) => Ok(doc.map(|doc| doc.into_token(purpose))),
+            Err(err) => Err(FindByUserError::Database(err.into())),
+        }
+    }
+
+    async fn consume(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: String,
+    ) -> Result<Option<OneTimeToken>, ConsumeError> {
+        if self.error {
+            return Err(ConsumeError::Database(DatabaseError::simulated()));
+        }
+
+        let results = self
+            .collection
+            .find_one_and_delete(
+                doc! {
+                    "purpose": purpose_name(purpose),
+                    "token_hash": token_hash,
+                    "expires_at": { "$gt": DateTime::now() },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(doc) => Ok(doc.map(|doc| doc.into_token(purpose))),
+            Err(err) => Err(ConsumeError::Database(err.into())),
+        }
+    }
+}
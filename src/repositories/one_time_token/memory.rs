@@ -0,0 +1,118 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::Utc;
+
+use crate::{
+    domain::user::entities::{OneTimeToken, TokenPurpose},
+    repositories::support::{new_id, now},
+};
+
+use super::{ConsumeError, CreateError, CreateInput, FindByUserError, Repository};
+
+struct StoredToken {
+    token: OneTimeToken,
+    token_hash: String,
+}
+
+/// Keeps tokens in this process, so any handed out are void once it stops.
+pub struct InMemoryRepository {
+    tokens: Mutex<Vec<StoredToken>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self {
+            tokens: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn create(&self, input: CreateInput) -> Result<OneTimeToken, CreateError> {
+        let token = OneTimeToken {
+            id: new_id(),
+            user_id: input.user_id,
+            purpose: input.purpose,
+            created_at: now(),
+            expires_at: input.expires_at,
+        };
+
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|stored| {
+            stored.token.user_id != token.user_id || stored.token.purpose != token.purpose
+        });
+        tokens.push(StoredToken {
+            token: token.clone(),
+            token_hash: input.token_hash,
+        });
+
+        Ok(token)
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: String,
+        purpose: TokenPurpose,
+    ) -> Result<Option<OneTimeToken>, FindByUserError> {
+        let tokens = self.tokens.lock().unwrap();
+
+        Ok(tokens
+            .iter()
+            .find(|stored| stored.token.user_id == user_id && stored.token.purpose == purpose)
+            .map(|stored| stored.token.clone()))
+    }
+
+    async fn consume(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: String,
+    ) -> Result<Option<OneTimeToken>, ConsumeError> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.lock().unwrap();
+
+        let position = tokens.iter().position(|stored| {
+            stored.token.purpose == purpose
+                && stored.token_hash == token_hash
+                && stored.token.expires_at > now
+        });
+
+        Ok(position.map(|position| tokens.remove(position).token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_only_consume_token_once() {
+        let repo = InMemoryRepository::new();
+        repo.create(CreateInput {
+            user_id: "user".to_string(),
+            purpose: TokenPurpose::PasswordReset,
+            token_hash: "hash".to_string(),
+            expires_at: Utc::now() + Duration::hours(1),
+        })
+        .await
+        .ok()
+        .unwrap();
+
+        let first = repo
+            .consume(TokenPurpose::PasswordReset, "hash".to_string())
+            .await
+            .ok()
+            .unwrap();
+        let second = repo
+            .consume(TokenPurpose::PasswordReset, "hash".to_string())
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(first.map(|token| token.user_id), Some("user".to_string()));
+        assert_eq!(second, None);
+    }
+}
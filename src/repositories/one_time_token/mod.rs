@@ -0,0 +1,124 @@
+#[cfg(feature = "mongo")]
+pub mod adapter;
+mod memory;
+#[cfg(feature = "sql")]
+mod sql;
+
+#[cfg(feature = "mongo")]
+pub use adapter::MongoRepository;
+pub use memory::InMemoryRepository;
+#[cfg(feature = "sql")]
+pub use sql::SqlRepository;
+
+use std::{error::Error, fmt};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+use mockall::*;
+
+use crate::{
+    domain::user::entities::{OneTimeToken, TokenPurpose},
+    repositories::error::DatabaseError,
+};
+
+pub struct CreateInput {
+    pub user_id: String,
+    pub purpose: TokenPurpose,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum CreateError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum ConsumeError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum FindByUserError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+impl fmt::Display for CreateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateError::Database(err) => write!(f, "could not create one time token: {}", err),
+        }
+    }
+}
+
+impl Error for CreateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CreateError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for ConsumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsumeError::Database(err) => write!(f, "could not consume one time token: {}", err),
+        }
+    }
+}
+
+impl Error for ConsumeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConsumeError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for FindByUserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindByUserError::Database(err) => write!(f, "could not find one time token: {}", err),
+        }
+    }
+}
+
+impl Error for FindByUserError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FindByUserError::Database(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(any(feature = "mongo", feature = "sql"))]
+fn purpose_name(purpose: TokenPurpose) -> &'static str {
+    match purpose {
+        TokenPurpose::PasswordReset => "password_reset",
+        TokenPurpose::EmailVerification => "email_verification",
+        TokenPurpose::SignInChallenge => "sign_in_challenge",
+    }
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait Repository: Send + Sync {
+    /// Stores a token, replacing any the user already had for the same purpose.
+    async fn create(&self, input: CreateInput) -> Result<OneTimeToken, CreateError>;
+    async fn find_by_user(
+        &self,
+        user_id: String,
+        purpose: TokenPurpose,
+    ) -> Result<Option<OneTimeToken>, FindByUserError>;
+    /// Atomically removes and returns an unexpired token, so it can only ever be used once.
+    async fn consume(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: String,
+    ) -> Result<Option<OneTimeToken>, ConsumeError>;
+}
@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::{
+    domain::user::entities::{OneTimeToken, TokenPurpose},
+    repositories::support::{new_id, now},
+};
+
+use super::{purpose_name, ConsumeError, CreateError, CreateInput, FindByUserError, Repository};
+
+pub struct SqlRepository {
+    pool: AnyPool,
+}
+
+impl SqlRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn token_from_row(row: &AnyRow, purpose: TokenPurpose) -> Result<OneTimeToken, sqlx::Error> {
+    Ok(OneTimeToken {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        purpose,
+        created_at: Utc.timestamp_millis(row.try_get("created_at")?),
+        expires_at: Utc.timestamp_millis(row.try_get("expires_at")?),
+    })
+}
+
+#[async_trait]
+impl Repository for SqlRepository {
+    async fn create(&self, input: CreateInput) -> Result<OneTimeToken, CreateError> {
+        let token = OneTimeToken {
+            id: new_id(),
+            user_id: input.user_id,
+            purpose: input.purpose,
+            created_at: now(),
+            expires_at: input.expires_at,
+        };
+
+        let results: Result<(), sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+
+            sqlx::query("DELETE FROM one_time_tokens WHERE user_id = $1 AND purpose = $2")
+                .bind(token.user_id.clone())
+                .bind(purpose_name(token.purpose))
+                .execute(&mut transaction)
+                .await?;
+            sqlx::query(
+                "INSERT INTO one_time_tokens (id, user_id, purpose, token_hash, created_at, \
+                 expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(token.id.clone())
+            .bind(token.user_id.clone())
+            .bind(purpose_name(token.purpose))
+            .bind(input.token_hash)
+            .bind(token.created_at.timestamp_millis())
+            .bind(token.expires_at.timestamp_millis())
+            .execute(&mut transaction)
+            .await?;
+
+            transaction.commit().await
+        }
+        .await;
+
+        match results {
+            Ok(()) => Ok(token),
+            Err(err) => Err(CreateError::Database(err.into())),
+        }
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: String,
+        purpose: TokenPurpose,
+    ) -> Result<Option<OneTimeToken>, FindByUserError> {
+        let results = sqlx::query(
+            "SELECT id, user_id, created_at, expires_at FROM one_time_tokens \
+             WHERE user_id = $1 AND purpose = $2",
+        )
+        .bind(user_id)
+        .bind(purpose_name(purpose))
+        .fetch_optional(&self.pool)
+        .await;
+
+        results
+            .and_then(|row| row.map(|row| token_from_row(&row, purpose)).transpose())
+            .map_err(|err| FindByUserError::Database(err.into()))
+    }
+
+    async fn consume(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: String,
+    ) -> Result<Option<OneTimeToken>, ConsumeError> {
+        let results = sqlx::query(
+            "DELETE FROM one_time_tokens WHERE purpose = $1 AND token_hash = $2 AND expires_at > $3 \
+             RETURNING id, user_id, created_at, expires_at",
+        )
+        .bind(purpose_name(purpose))
+        .bind(token_hash)
+        .bind(Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .await;
+
+        results
+            .and_then(|row| row.map(|row| token_from_row(&row, purpose)).transpose())
+            .map_err(|err| ConsumeError::Database(err.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::repositories::support::test_pool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_only_consume_unexpired_token_once() {
+        let repo = SqlRepository::new(test_pool().await);
+        for (user_id, purpose, expires_at) in [
+            (
+                "user",
+                TokenPurpose::PasswordReset,
+                Utc::now() + Duration::hours(1),
+            ),
+            (
+                "other",
+                TokenPurpose::EmailVerification,
+                Utc::now() - Duration::hours(1),
+            ),
+        ] {
+            repo.create(CreateInput {
+                user_id: user_id.to_string(),
+                purpose,
+                token_hash: format!("{}-hash", user_id),
+                expires_at,
+            })
+            .await
+            .ok()
+            .unwrap();
+        }
+
+        let consume = |purpose, hash: &str| repo.consume(purpose, hash.to_string());
+        let first = consume(TokenPurpose::PasswordReset, "user-hash").await.ok();
+        let second = consume(TokenPurpose::PasswordReset, "user-hash").await.ok();
+        let expired = consume(TokenPurpose::EmailVerification, "other-hash")
+            .await
+            .ok();
+
+        assert_eq!(
+            first.flatten().map(|token| token.user_id),
+            Some("user".to_string())
+        );
+        assert_eq!(second, Some(None));
+        assert_eq!(expired, Some(None));
+    }
+}
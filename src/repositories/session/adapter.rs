@@ -0,0 +1,252 @@
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::IndexOptions,
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::user::entities::Session, repositories::error::DatabaseError};
+
+use super::{
+    CreateError, CreateInput, FindByIdError, FindByRefreshTokenError, Repository, RevokeError,
+    RotateError,
+};
+
+pub struct MongoRepository {
+    collection: Collection<SessionDocument>,
+    error: bool,
+}
+
+impl MongoRepository {
+    pub fn new(db: Database, collection: &str) -> Self {
+        Self {
+            error: false,
+            collection: db.collection(collection),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct SessionDocument {
+    _id: ObjectId,
+    user_id: String,
+    refresh_token_hash: String,
+    previous_refresh_token_hashes: Vec<String>,
+    revoked: bool,
+    created_at: DateTime,
+    expires_at: DateTime,
+}
+
+impl From<SessionDocument> for Session {
+    fn from(doc: SessionDocument) -> Self {
+        Self {
+            id: doc._id.to_hex(),
+            user_id: doc.user_id,
+            refresh_token_hash: doc.refresh_token_hash,
+            revoked: doc.revoked,
+            expires_at: Utc.timestamp_millis(doc.expires_at.timestamp_millis()),
+        }
+    }
+}
+
+/// Creates the indexes behind every refresh token lookup, including the one that makes refresh
+/// tokens unique. Does nothing for indexes that already exist.
+pub async fn ensure_indexes(database: &Database, collection: &str) -> mongodb::error::Result<()> {
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "refresh_token_hash": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("refresh_token_hash_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        // Lets reuse of an already rotated refresh token be caught without a collection scan
+        IndexModel::builder()
+            .keys(doc! { "previous_refresh_token_hashes": 1 })
+            .build(),
+        IndexModel::builder().keys(doc! { "user_id": 1 }).build(),
+    ];
+
+    database
+        .collection::<Document>(collection)
+        .create_indexes(indexes, None)
+        .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Repository for MongoRepository {
+    async fn create(&self, input: CreateInput) -> Result<Session, CreateError> {
+        if self.error {
+            return Err(CreateError::Database(DatabaseError::simulated()));
+        }
+
+        let now = SystemTime::now();
+
+        let new_doc = SessionDocument {
+            _id: ObjectId::new(),
+            user_id: input.user_id,
+            refresh_token_hash: input.refresh_token_hash,
+            previous_refresh_token_hashes: vec![],
+            revoked: false,
+            created_at: DateTime::from_system_time(now),
+            expires_at: DateTime::from_millis(input.expires_at.timestamp_millis()),
+        };
+
+        let results = self.collection.insert_one(&new_doc, None).await;
+
+        match results {
+            Ok(_) => Ok(new_doc.into()),
+            Err(err) => Err(CreateError::Database(err.into())),
+        }
+    }
+
+    async fn find_by_id(&self, id: String) -> Result<Session, FindByIdError> {
+        if self.error {
+            return Err(FindByIdError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(FindByIdError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .find_one(Some(doc! { "_id": id }), None)
+            .await;
+
+        match results {
+            Ok(Some(doc)) => Ok(doc.into()),
+            Ok(None) => Err(FindByIdError::NotFound),
+            Err(err) => Err(FindByIdError::Database(err.into())),
+        }
+    }
+
+    async fn find_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: String,
+    ) -> Result<Option<Session>, FindByRefreshTokenError> {
+        if self.error {
+            return Err(FindByRefreshTokenError::Database(DatabaseError::simulated()));
+        }
+
+        let results = self
+            .collection
+            .find_one(
+                Some(doc! {
+                    "$or": [
+                        { "refresh_token_hash": &refresh_token_hash },
+                        { "previous_refresh_token_hashes": &refresh_token_hash },
+                    ]
+                }),
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(doc) => Ok(doc.map(Session::from)),
+            Err(err) => Err(FindByRefreshTokenError::Database(err.into())),
+        }
+    }
+
+    async fn rotate(
+        &self,
+        id: String,
+        current_refresh_token_hash: String,
+        new_refresh_token_hash: String,
+    ) -> Result<(), RotateError> {
+        if self.error {
+            return Err(RotateError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(RotateError::InvalidId),
+        };
+
+        // Only matches while the presented token is still current, so concurrent rotations of the
+        // same token cannot both succeed
+        let results = self
+            .collection
+            .update_one(
+                doc! {
+                    "_id": id,
+                    "refresh_token_hash": &current_refresh_token_hash,
+                    "revoked": false,
+                },
+                doc! {
+                    "$set": { "refresh_token_hash": new_refresh_token_hash },
+                    "$push": { "previous_refresh_token_hashes": current_refresh_token_hash },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) if update_result.matched_count == 0 => Err(RotateError::Stale),
+            Ok(_) => Ok(()),
+            Err(err) => Err(RotateError::Database(err.into())),
+        }
+    }
+
+    async fn revoke(&self, id: String) -> Result<(), RevokeError> {
+        if self.error {
+            return Err(RevokeError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(RevokeError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "revoked": true } },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RevokeError::Database(err.into())),
+        }
+    }
+
+    async fn revoke_all_for_user(
+        &self,
+        user_id: String,
+        except_id: Option<String>,
+    ) -> Result<(), RevokeError> {
+        if self.error {
+            return Err(RevokeError::Database(DatabaseError::simulated()));
+        }
+
+        let mut filter = doc! { "user_id": user_id, "revoked": false };
+        if let Some(except_id) = except_id {
+            match ObjectId::parse_str(except_id) {
+                Ok(except_id) => filter.insert("_id", doc! { "$ne": except_id }),
+                Err(_) => return Err(RevokeError::InvalidId),
+            };
+        }
+
+        let results = self
+            .collection
+            .update_many(filter, doc! { "$set": { "revoked": true } }, None)
+            .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RevokeError::Database(err.into())),
+        }
+    }
+}
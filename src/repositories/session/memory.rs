@@ -0,0 +1,182 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::{
+    domain::user::entities::Session,
+    repositories::support::{new_id, parse_id},
+};
+
+use super::{
+    CreateError, CreateInput, FindByIdError, FindByRefreshTokenError, Repository, RevokeError,
+    RotateError,
+};
+
+struct StoredSession {
+    session: Session,
+    previous_refresh_token_hashes: Vec<String>,
+}
+
+/// Keeps sessions in this process, so everyone is signed out once it stops.
+pub struct InMemoryRepository {
+    sessions: Mutex<HashMap<String, StoredSession>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn create(&self, input: CreateInput) -> Result<Session, CreateError> {
+        let session = Session {
+            id: new_id(),
+            user_id: input.user_id,
+            refresh_token_hash: input.refresh_token_hash,
+            revoked: false,
+            expires_at: input.expires_at,
+        };
+
+        self.sessions.lock().unwrap().insert(
+            session.id.clone(),
+            StoredSession {
+                session: session.clone(),
+                previous_refresh_token_hashes: vec![],
+            },
+        );
+
+        Ok(session)
+    }
+
+    async fn find_by_id(&self, id: String) -> Result<Session, FindByIdError> {
+        let id = parse_id(&id).ok_or(FindByIdError::InvalidId)?;
+
+        let sessions = self.sessions.lock().unwrap();
+
+        match sessions.get(&id) {
+            Some(stored) => Ok(stored.session.clone()),
+            None => Err(FindByIdError::NotFound),
+        }
+    }
+
+    async fn find_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: String,
+    ) -> Result<Option<Session>, FindByRefreshTokenError> {
+        let sessions = self.sessions.lock().unwrap();
+
+        Ok(sessions
+            .values()
+            .find(|stored| {
+                stored.session.refresh_token_hash == refresh_token_hash
+                    || stored
+                        .previous_refresh_token_hashes
+                        .contains(&refresh_token_hash)
+            })
+            .map(|stored| stored.session.clone()))
+    }
+
+    async fn rotate(
+        &self,
+        id: String,
+        current_refresh_token_hash: String,
+        new_refresh_token_hash: String,
+    ) -> Result<(), RotateError> {
+        let id = parse_id(&id).ok_or(RotateError::InvalidId)?;
+
+        let mut sessions = self.sessions.lock().unwrap();
+
+        match sessions.get_mut(&id) {
+            Some(stored)
+                if !stored.session.revoked
+                    && stored.session.refresh_token_hash == current_refresh_token_hash =>
+            {
+                stored.session.refresh_token_hash = new_refresh_token_hash;
+                stored
+                    .previous_refresh_token_hashes
+                    .push(current_refresh_token_hash);
+                Ok(())
+            }
+            _ => Err(RotateError::Stale),
+        }
+    }
+
+    async fn revoke(&self, id: String) -> Result<(), RevokeError> {
+        let id = parse_id(&id).ok_or(RevokeError::InvalidId)?;
+
+        if let Some(stored) = self.sessions.lock().unwrap().get_mut(&id) {
+            stored.session.revoked = true;
+        }
+
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(
+        &self,
+        user_id: String,
+        except_id: Option<String>,
+    ) -> Result<(), RevokeError> {
+        let except_id = match except_id {
+            Some(except_id) => Some(parse_id(&except_id).ok_or(RevokeError::InvalidId)?),
+            None => None,
+        };
+
+        let mut sessions = self.sessions.lock().unwrap();
+
+        for stored in sessions.values_mut() {
+            if stored.session.user_id == user_id && Some(&stored.session.id) != except_id.as_ref() {
+                stored.session.revoked = true;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_only_rotate_current_refresh_token_once() {
+        let repo = InMemoryRepository::new();
+        let session = repo
+            .create(CreateInput {
+                user_id: "user".to_string(),
+                refresh_token_hash: "first".to_string(),
+                expires_at: Utc::now() + Duration::days(1),
+            })
+            .await
+            .ok()
+            .unwrap();
+
+        let rotated = repo
+            .rotate(
+                session.id.clone(),
+                "first".to_string(),
+                "second".to_string(),
+            )
+            .await;
+        let replayed = repo
+            .rotate(session.id.clone(), "first".to_string(), "third".to_string())
+            .await;
+
+        assert!(rotated.is_ok());
+        assert!(matches!(replayed, Err(RotateError::Stale)));
+        let found = repo
+            .find_by_refresh_token_hash("first".to_string())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            found.map(|found| found.refresh_token_hash),
+            Some("second".to_string())
+        );
+    }
+}
@@ -0,0 +1,179 @@
+#[cfg(feature = "mongo")]
+pub mod adapter;
+mod memory;
+#[cfg(feature = "sql")]
+mod sql;
+
+#[cfg(feature = "mongo")]
+pub use adapter::MongoRepository;
+pub use memory::InMemoryRepository;
+#[cfg(feature = "sql")]
+pub use sql::SqlRepository;
+
+use std::{error::Error, fmt};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+use mockall::*;
+
+use crate::{domain::user::entities::Session, repositories::error::DatabaseError};
+
+pub struct CreateInput {
+    pub user_id: String,
+    pub refresh_token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum CreateError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum FindByIdError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum FindByRefreshTokenError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum RotateError {
+    InvalidId,
+    /// The session was revoked or its refresh token already rotated by someone else
+    Stale,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum RevokeError {
+    InvalidId,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+impl fmt::Display for CreateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateError::Database(err) => write!(f, "could not create session: {}", err),
+        }
+    }
+}
+
+impl Error for CreateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CreateError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for FindByIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindByIdError::InvalidId => f.write_str("invalid session id"),
+            FindByIdError::NotFound => f.write_str("session not found"),
+            FindByIdError::Database(err) => write!(f, "could not find session: {}", err),
+        }
+    }
+}
+
+impl Error for FindByIdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FindByIdError::InvalidId | FindByIdError::NotFound => None,
+            FindByIdError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for FindByRefreshTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindByRefreshTokenError::Database(err) => {
+                write!(f, "could not find session by refresh token: {}", err)
+            }
+        }
+    }
+}
+
+impl Error for FindByRefreshTokenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FindByRefreshTokenError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for RotateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RotateError::InvalidId => f.write_str("invalid session id"),
+            RotateError::Stale => f.write_str("refresh token is no longer current"),
+            RotateError::Database(err) => write!(f, "could not rotate refresh token: {}", err),
+        }
+    }
+}
+
+impl Error for RotateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RotateError::InvalidId | RotateError::Stale => None,
+            RotateError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for RevokeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevokeError::InvalidId => f.write_str("invalid session id"),
+            RevokeError::Database(err) => write!(f, "could not revoke session: {}", err),
+        }
+    }
+}
+
+impl Error for RevokeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RevokeError::InvalidId => None,
+            RevokeError::Database(err) => Some(err),
+        }
+    }
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait Repository: Send + Sync {
+    async fn create(&self, input: CreateInput) -> Result<Session, CreateError>;
+    async fn find_by_id(&self, id: String) -> Result<Session, FindByIdError>;
+    /// Finds the session a refresh token was issued for, whether it is the current token or one
+    /// that has already been rotated.
+    async fn find_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: String,
+    ) -> Result<Option<Session>, FindByRefreshTokenError>;
+    async fn rotate(
+        &self,
+        id: String,
+        current_refresh_token_hash: String,
+        new_refresh_token_hash: String,
+    ) -> Result<(), RotateError>;
+    async fn revoke(&self, id: String) -> Result<(), RevokeError>;
+    /// Revokes every session of the user, apart from `except_id` when one is given.
+    async fn revoke_all_for_user(
+        &self,
+        user_id: String,
+        except_id: Option<String>,
+    ) -> Result<(), RevokeError>;
+}
@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::{
+    domain::user::entities::Session,
+    repositories::support::{new_id, now, parse_id},
+};
+
+use super::{
+    CreateError, CreateInput, FindByIdError, FindByRefreshTokenError, Repository, RevokeError,
+    RotateError,
+};
+
+const SESSION_COLUMNS: &str = "id, user_id, refresh_token_hash, revoked, expires_at";
+
+pub struct SqlRepository {
+    pool: AnyPool,
+}
+
+impl SqlRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn session_from_row(row: &AnyRow) -> Result<Session, sqlx::Error> {
+    Ok(Session {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        refresh_token_hash: row.try_get("refresh_token_hash")?,
+        revoked: row.try_get("revoked")?,
+        expires_at: Utc.timestamp_millis(row.try_get("expires_at")?),
+    })
+}
+
+#[async_trait]
+impl Repository for SqlRepository {
+    async fn create(&self, input: CreateInput) -> Result<Session, CreateError> {
+        let session = Session {
+            id: new_id(),
+            user_id: input.user_id,
+            refresh_token_hash: input.refresh_token_hash,
+            revoked: false,
+            expires_at: input.expires_at,
+        };
+
+        let results = sqlx::query(
+            "INSERT INTO sessions (id, user_id, refresh_token_hash, revoked, created_at, \
+             expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(session.id.clone())
+        .bind(session.user_id.clone())
+        .bind(session.refresh_token_hash.clone())
+        .bind(false)
+        .bind(now().timestamp_millis())
+        .bind(session.expires_at.timestamp_millis())
+        .execute(&self.pool)
+        .await;
+
+        match results {
+            Ok(_) => Ok(session),
+            Err(err) => Err(CreateError::Database(err.into())),
+        }
+    }
+
+    async fn find_by_id(&self, id: String) -> Result<Session, FindByIdError> {
+        let id = parse_id(&id).ok_or(FindByIdError::InvalidId)?;
+
+        let sql = format!("SELECT {} FROM sessions WHERE id = $1", SESSION_COLUMNS);
+        let results = sqlx::query(&sql).bind(id).fetch_optional(&self.pool).await;
+
+        match results.and_then(|row| row.as_ref().map(session_from_row).transpose()) {
+            Ok(Some(session)) => Ok(session),
+            Ok(None) => Err(FindByIdError::NotFound),
+            Err(err) => Err(FindByIdError::Database(err.into())),
+        }
+    }
+
+    async fn find_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: String,
+    ) -> Result<Option<Session>, FindByRefreshTokenError> {
+        let sql = format!(
+            "SELECT {} FROM sessions WHERE refresh_token_hash = $1 OR id IN \
+             (SELECT session_id FROM previous_refresh_tokens WHERE refresh_token_hash = $2)",
+            SESSION_COLUMNS
+        );
+        let results = sqlx::query(&sql)
+            .bind(refresh_token_hash.clone())
+            .bind(refresh_token_hash)
+            .fetch_optional(&self.pool)
+            .await;
+
+        results
+            .and_then(|row| row.as_ref().map(session_from_row).transpose())
+            .map_err(|err| FindByRefreshTokenError::Database(err.into()))
+    }
+
+    async fn rotate(
+        &self,
+        id: String,
+        current_refresh_token_hash: String,
+        new_refresh_token_hash: String,
+    ) -> Result<(), RotateError> {
+        let id = parse_id(&id).ok_or(RotateError::InvalidId)?;
+
+        // Only matches while the presented token is still current, so concurrent rotations of the
+        // same token cannot both succeed
+        let results: Result<bool, sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+
+            let updated = sqlx::query(
+                "UPDATE sessions SET refresh_token_hash = $1 \
+                 WHERE id = $2 AND refresh_token_hash = $3 AND revoked = $4",
+            )
+            .bind(new_refresh_token_hash)
+            .bind(id.clone())
+            .bind(current_refresh_token_hash.clone())
+            .bind(false)
+            .execute(&mut transaction)
+            .await?;
+            if updated.rows_affected() == 0 {
+                return Ok(false);
+            }
+
+            sqlx::query(
+                "INSERT INTO previous_refresh_tokens (refresh_token_hash, session_id) \
+                 VALUES ($1, $2)",
+            )
+            .bind(current_refresh_token_hash)
+            .bind(id)
+            .execute(&mut transaction)
+            .await?;
+
+            transaction.commit().await?;
+            Ok(true)
+        }
+        .await;
+
+        match results {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(RotateError::Stale),
+            Err(err) => Err(RotateError::Database(err.into())),
+        }
+    }
+
+    async fn revoke(&self, id: String) -> Result<(), RevokeError> {
+        let id = parse_id(&id).ok_or(RevokeError::InvalidId)?;
+
+        let results = sqlx::query("UPDATE sessions SET revoked = $1 WHERE id = $2")
+            .bind(true)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RevokeError::Database(err.into())),
+        }
+    }
+
+    async fn revoke_all_for_user(
+        &self,
+        user_id: String,
+        except_id: Option<String>,
+    ) -> Result<(), RevokeError> {
+        let except_id = match except_id {
+            Some(except_id) => Some(parse_id(&except_id).ok_or(RevokeError::InvalidId)?),
+            None => None,
+        };
+
+        let query = match except_id {
+            Some(except_id) => {
+                sqlx::query("UPDATE sessions SET revoked = $1 WHERE user_id = $2 AND id <> $3")
+                    .bind(true)
+                    .bind(user_id)
+                    .bind(except_id)
+            }
+            None => sqlx::query("UPDATE sessions SET revoked = $1 WHERE user_id = $2")
+                .bind(true)
+                .bind(user_id),
+        };
+
+        match query.execute(&self.pool).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RevokeError::Database(err.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::repositories::support::test_pool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_only_rotate_current_refresh_token_once() {
+        let repo = SqlRepository::new(test_pool().await);
+        let session = repo
+            .create(CreateInput {
+                user_id: "user".to_string(),
+                refresh_token_hash: "first".to_string(),
+                expires_at: Utc::now() + Duration::days(1),
+            })
+            .await
+            .ok()
+            .unwrap();
+
+        let rotated = repo
+            .rotate(
+                session.id.clone(),
+                "first".to_string(),
+                "second".to_string(),
+            )
+            .await;
+        let replayed = repo
+            .rotate(session.id.clone(), "first".to_string(), "third".to_string())
+            .await;
+
+        assert!(rotated.is_ok());
+        assert!(matches!(replayed, Err(RotateError::Stale)));
+        let found = repo
+            .find_by_refresh_token_hash("first".to_string())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            found.map(|found| found.refresh_token_hash),
+            Some("second".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn should_revoke_all_but_excepted_session() {
+        let repo = SqlRepository::new(test_pool().await);
+        let mut ids = Vec::new();
+        for hash in ["first", "second"] {
+            let session = repo
+                .create(CreateInput {
+                    user_id: "user".to_string(),
+                    refresh_token_hash: hash.to_string(),
+                    expires_at: Utc::now() + Duration::days(1),
+                })
+                .await
+                .ok()
+                .unwrap();
+            ids.push(session.id);
+        }
+
+        assert!(repo
+            .revoke_all_for_user("user".to_string(), Some(ids[1].clone()))
+            .await
+            .is_ok());
+
+        let revoked = repo.find_by_id(ids[0].clone()).await.ok().unwrap();
+        let kept = repo.find_by_id(ids[1].clone()).await.ok().unwrap();
+        assert!(revoked.revoked);
+        assert!(!kept.revoked);
+    }
+
+    #[tokio::test]
+    async fn should_not_create_two_sessions_with_same_refresh_token() {
+        let repo = SqlRepository::new(test_pool().await);
+        let input = || CreateInput {
+            user_id: "user".to_string(),
+            refresh_token_hash: "first".to_string(),
+            expires_at: Utc::now() + Duration::days(1),
+        };
+
+        assert!(repo.create(input()).await.is_ok());
+        assert!(matches!(
+            repo.create(input()).await,
+            Err(CreateError::Database(_))
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use chrono::{DateTime as ChronoDateTime, TimeZone, Utc};
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument},
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::user::entities::SignInAttempts,
+    repositories::error::{DatabaseError, DatabaseErrorKind},
+};
+
+use super::{
+    subject_key, ClearError, FindError, LockError, RecordFailureError, Repository, Subject,
+};
+
+pub struct MongoRepository {
+    collection: Collection<SignInAttemptsDocument>,
+    error: bool,
+}
+
+impl MongoRepository {
+    pub fn new(db: Database, collection: &str) -> Self {
+        Self {
+            error: false,
+            collection: db.collection(collection),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct SignInAttemptsDocument {
+    subject: String,
+    failures: i64,
+    locked_until: Option<DateTime>,
+}
+
+impl From<SignInAttemptsDocument> for SignInAttempts {
+    fn from(doc: SignInAttemptsDocument) -> Self {
+        Self {
+            failures: u32::try_from(doc.failures).unwrap_or(u32::MAX),
+            locked_until: doc
+                .locked_until
+                .map(|locked_until| Utc.timestamp_millis(locked_until.timestamp_millis())),
+        }
+    }
+}
+
+/// Creates the unique index on `subject` that keeps concurrent upserts from adding the same
+/// subject twice. Does nothing if it already exists.
+pub async fn ensure_indexes(database: &Database, collection: &str) -> mongodb::error::Result<()> {
+    let subject_index = IndexModel::builder()
+        .keys(doc! { "subject": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("subject_unique".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build();
+
+    database
+        .collection::<Document>(collection)
+        .create_index(subject_index, None)
+        .await?;
+
+    Ok(())
+}
+
+fn to_bson_date(date: ChronoDateTime<Utc>) -> DateTime {
+    DateTime::from_millis(date.timestamp_millis())
+}
+
+#[async_trait]
+impl Repository for MongoRepository {
+    async fn find(&self, subject: Subject) -> Result<Option<SignInAttempts>, FindError> {
+        if self.error {
+            return Err(FindError::Database(DatabaseError::simulated()));
+        }
+
+        let results = self
+            .collection
+            .find_one(doc! { "subject": subject_key(subject) }, None)
+            .await;
+
+        match results {
+            Ok(doc) => Ok(doc.map(SignInAttempts::from)),
+            Err(err) => Err(FindError::Database(err.into())),
+        }
+    }
+
+    async fn record_failure(
+        &self,
+        subject: Subject,
+        since: ChronoDateTime<Utc>,
+    ) -> Result<SignInAttempts, RecordFailureError> {
+        if self.error {
+            return Err(RecordFailureError::Database(DatabaseError::simulated()));
+        }
+
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let results = self
+            .collection
+            .find_one_and_update(
+                doc! { "subject": subject_key(subject) },
+                // A pipeline, so the count can start over depending on the stored last failure
+                vec![doc! {
+                    "$set": {
+                        "failures": {
+                            "$cond": [
+                                { "$gte": ["$last_failure_at", to_bson_date(since)] },
+                                { "$add": ["$failures", 1_i64] },
+                                1_i64,
+                            ]
+                        },
+                        "last_failure_at": DateTime::now(),
+                        "locked_until": { "$ifNull": ["$locked_until", null] },
+                    }
+                }],
+                options,
+            )
+            .await;
+
+        match results {
+            Ok(Some(doc)) => Ok(doc.into()),
+            // Only possible if the upsert somehow didn't write
+            Ok(None) => Err(RecordFailureError::Database(DatabaseError::new(
+                DatabaseErrorKind::Other,
+                "upsert returned no document",
+            ))),
+            Err(err) => Err(RecordFailureError::Database(err.into())),
+        }
+    }
+
+    async fn lock(&self, subject: Subject, until: ChronoDateTime<Utc>) -> Result<(), LockError> {
+        if self.error {
+            return Err(LockError::Database(DatabaseError::simulated()));
+        }
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "subject": subject_key(subject) },
+                doc! { "$set": { "locked_until": to_bson_date(until) } },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(LockError::Database(err.into())),
+        }
+    }
+
+    async fn clear(&self, subject: Subject) -> Result<(), ClearError> {
+        if self.error {
+            return Err(ClearError::Database(DatabaseError::simulated()));
+        }
+
+        let results = self
+            .collection
+            .delete_one(doc! { "subject": subject_key(subject) }, None)
+            .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(ClearError::Database(err.into())),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::domain::user::entities::SignInAttempts;
+
+use super::{ClearError, FindError, LockError, RecordFailureError, Repository, Subject};
+
+/// Keeps failed sign-ins in this process, so lockouts are lifted once it stops.
+pub struct InMemoryRepository {
+    attempts: Mutex<HashMap<Subject, Record>>,
+}
+
+struct Record {
+    attempts: SignInAttempts,
+    last_failure_at: DateTime<Utc>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self {
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn find(&self, subject: Subject) -> Result<Option<SignInAttempts>, FindError> {
+        Ok(self
+            .attempts
+            .lock()
+            .unwrap()
+            .get(&subject)
+            .map(|record| record.attempts.clone()))
+    }
+
+    async fn record_failure(
+        &self,
+        subject: Subject,
+        since: DateTime<Utc>,
+    ) -> Result<SignInAttempts, RecordFailureError> {
+        let mut attempts = self.attempts.lock().unwrap();
+        let now = Utc::now();
+
+        let record = attempts.entry(subject).or_insert(Record {
+            attempts: SignInAttempts {
+                failures: 0,
+                locked_until: None,
+            },
+            last_failure_at: now,
+        });
+        if record.last_failure_at < since {
+            record.attempts.failures = 0;
+        }
+        record.attempts.failures += 1;
+        record.last_failure_at = now;
+
+        Ok(record.attempts.clone())
+    }
+
+    async fn lock(&self, subject: Subject, until: DateTime<Utc>) -> Result<(), LockError> {
+        if let Some(record) = self.attempts.lock().unwrap().get_mut(&subject) {
+            record.attempts.locked_until = Some(until);
+        }
+
+        Ok(())
+    }
+
+    async fn clear(&self, subject: Subject) -> Result<(), ClearError> {
+        self.attempts.lock().unwrap().remove(&subject);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_count_failures_until_cleared() {
+        let repo = InMemoryRepository::new();
+        let subject = Subject::Email("someone@example.com".to_string());
+        let until = Utc::now() + Duration::minutes(5);
+        let since = Utc::now() - Duration::hours(1);
+
+        repo.record_failure(subject.clone(), since)
+            .await
+            .ok()
+            .unwrap();
+        let second = repo
+            .record_failure(subject.clone(), since)
+            .await
+            .ok()
+            .unwrap();
+        assert!(repo.lock(subject.clone(), until).await.is_ok());
+        let locked = repo.find(subject.clone()).await.ok().unwrap();
+        assert!(repo.clear(subject.clone()).await.is_ok());
+        let cleared = repo.find(subject).await.ok().unwrap();
+
+        assert_eq!(second.failures, 2);
+        assert_eq!(
+            locked,
+            Some(SignInAttempts {
+                failures: 2,
+                locked_until: Some(until),
+            })
+        );
+        assert_eq!(cleared, None);
+    }
+
+    #[tokio::test]
+    async fn should_start_counting_over_after_the_window() {
+        let repo = InMemoryRepository::new();
+        let subject = Subject::Account("id".to_string());
+
+        repo.record_failure(subject.clone(), Utc::now())
+            .await
+            .ok()
+            .unwrap();
+        let results = repo
+            .record_failure(subject, Utc::now() + Duration::seconds(1))
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(results.failures, 1);
+    }
+
+    #[tokio::test]
+    async fn should_count_subjects_separately() {
+        let repo = InMemoryRepository::new();
+        let since = Utc::now() - Duration::hours(1);
+
+        repo.record_failure(Subject::Account("id".to_string()), since)
+            .await
+            .ok()
+            .unwrap();
+        let results = repo
+            .record_failure(Subject::Email("id".to_string()), since)
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(results.failures, 1);
+        assert_eq!(
+            repo.find(Subject::Account("other".to_string()))
+                .await
+                .ok()
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_lock_subject_without_failures() {
+        let repo = InMemoryRepository::new();
+        let subject = Subject::Account("id".to_string());
+
+        assert!(repo
+            .lock(subject.clone(), Utc::now() + Duration::minutes(5))
+            .await
+            .is_ok());
+
+        assert_eq!(repo.find(subject).await.ok().unwrap(), None);
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(feature = "mongo")]
+pub mod adapter;
+mod memory;
+#[cfg(feature = "sql")]
+mod sql;
+
+#[cfg(feature = "mongo")]
+pub use adapter::MongoRepository;
+pub use memory::InMemoryRepository;
+#[cfg(feature = "sql")]
+pub use sql::SqlRepository;
+
+use std::{error::Error, fmt};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+use mockall::*;
+
+use crate::{domain::user::entities::SignInAttempts, repositories::error::DatabaseError};
+
+/// What failed sign-ins are counted against.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum Subject {
+    /// An existing user, by id
+    Account(String),
+    /// An email as typed into sign in, whether or not an account has it
+    Email(String),
+}
+
+#[derive(Debug)]
+pub enum FindError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum RecordFailureError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum ClearError {
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+impl fmt::Display for FindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindError::Database(err) => write!(f, "could not find sign in attempts: {}", err),
+        }
+    }
+}
+
+impl Error for FindError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FindError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for RecordFailureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordFailureError::Database(err) => {
+                write!(f, "could not record failed sign in: {}", err)
+            }
+        }
+    }
+}
+
+impl Error for RecordFailureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RecordFailureError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Database(err) => write!(f, "could not lock subject: {}", err),
+        }
+    }
+}
+
+impl Error for LockError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LockError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for ClearError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClearError::Database(err) => write!(f, "could not clear sign in attempts: {}", err),
+        }
+    }
+}
+
+impl Error for ClearError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClearError::Database(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(any(feature = "mongo", feature = "sql"))]
+fn subject_key(subject: Subject) -> String {
+    match subject {
+        Subject::Account(id) => format!("account:{}", id),
+        Subject::Email(email) => format!("email:{}", email),
+    }
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait Repository: Send + Sync {
+    async fn find(&self, subject: Subject) -> Result<Option<SignInAttempts>, FindError>;
+    /// Atomically counts another failure, returning the updated record. Counting starts over from
+    /// one when the subject's previous failure was before `since`.
+    async fn record_failure(
+        &self,
+        subject: Subject,
+        since: DateTime<Utc>,
+    ) -> Result<SignInAttempts, RecordFailureError>;
+    async fn lock(&self, subject: Subject, until: DateTime<Utc>) -> Result<(), LockError>;
+    /// Forgets every failure, lifting any lock.
+    async fn clear(&self, subject: Subject) -> Result<(), ClearError>;
+}
@@ -0,0 +1,152 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::domain::user::entities::SignInAttempts;
+
+use super::{
+    subject_key, ClearError, FindError, LockError, RecordFailureError, Repository, Subject,
+};
+
+pub struct SqlRepository {
+    pool: AnyPool,
+}
+
+impl SqlRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn attempts_from_row(row: &AnyRow) -> Result<SignInAttempts, sqlx::Error> {
+    Ok(SignInAttempts {
+        failures: u32::try_from(row.try_get::<i64, _>("failures")?).unwrap_or(u32::MAX),
+        locked_until: row
+            .try_get::<Option<i64>, _>("locked_until")?
+            .map(|locked_until| Utc.timestamp_millis(locked_until)),
+    })
+}
+
+#[async_trait]
+impl Repository for SqlRepository {
+    async fn find(&self, subject: Subject) -> Result<Option<SignInAttempts>, FindError> {
+        let results =
+            sqlx::query("SELECT failures, locked_until FROM sign_in_attempts WHERE subject = $1")
+                .bind(subject_key(subject))
+                .fetch_optional(&self.pool)
+                .await;
+
+        results
+            .and_then(|row| row.as_ref().map(attempts_from_row).transpose())
+            .map_err(|err| FindError::Database(err.into()))
+    }
+
+    async fn record_failure(
+        &self,
+        subject: Subject,
+        since: DateTime<Utc>,
+    ) -> Result<SignInAttempts, RecordFailureError> {
+        let results = sqlx::query(
+            "INSERT INTO sign_in_attempts (subject, failures, last_failure_at) VALUES ($1, 1, $2) \
+             ON CONFLICT (subject) DO UPDATE SET \
+             failures = CASE WHEN sign_in_attempts.last_failure_at >= $3 \
+             THEN sign_in_attempts.failures + 1 ELSE 1 END, \
+             last_failure_at = $2 \
+             RETURNING failures, locked_until",
+        )
+        .bind(subject_key(subject))
+        .bind(Utc::now().timestamp_millis())
+        .bind(since.timestamp_millis())
+        .fetch_one(&self.pool)
+        .await;
+
+        results
+            .and_then(|row| attempts_from_row(&row))
+            .map_err(|err| RecordFailureError::Database(err.into()))
+    }
+
+    async fn lock(&self, subject: Subject, until: DateTime<Utc>) -> Result<(), LockError> {
+        let results =
+            sqlx::query("UPDATE sign_in_attempts SET locked_until = $1 WHERE subject = $2")
+                .bind(until.timestamp_millis())
+                .bind(subject_key(subject))
+                .execute(&self.pool)
+                .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(LockError::Database(err.into())),
+        }
+    }
+
+    async fn clear(&self, subject: Subject) -> Result<(), ClearError> {
+        let results = sqlx::query("DELETE FROM sign_in_attempts WHERE subject = $1")
+            .bind(subject_key(subject))
+            .execute(&self.pool)
+            .await;
+
+        match results {
+            Ok(_) => Ok(()),
+            Err(err) => Err(ClearError::Database(err.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::repositories::support::test_pool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_count_failures_until_cleared() {
+        let repo = SqlRepository::new(test_pool().await);
+        let subject = Subject::Email("someone@example.com".to_string());
+        let until = Utc.timestamp_millis((Utc::now() + Duration::minutes(5)).timestamp_millis());
+        let since = Utc::now() - Duration::hours(1);
+
+        repo.record_failure(subject.clone(), since)
+            .await
+            .ok()
+            .unwrap();
+        let second = repo
+            .record_failure(subject.clone(), since)
+            .await
+            .ok()
+            .unwrap();
+        assert!(repo.lock(subject.clone(), until).await.is_ok());
+        let locked = repo.find(subject.clone()).await.ok().unwrap();
+        assert!(repo.clear(subject.clone()).await.is_ok());
+        let cleared = repo.find(subject).await.ok().unwrap();
+
+        assert_eq!(second.failures, 2);
+        assert_eq!(
+            locked,
+            Some(SignInAttempts {
+                failures: 2,
+                locked_until: Some(until),
+            })
+        );
+        assert_eq!(cleared, None);
+    }
+
+    #[tokio::test]
+    async fn should_start_counting_over_after_the_window() {
+        let repo = SqlRepository::new(test_pool().await);
+        let subject = Subject::Account("id".to_string());
+
+        repo.record_failure(subject.clone(), Utc::now())
+            .await
+            .ok()
+            .unwrap();
+        let results = repo
+            .record_failure(subject, Utc::now() + Duration::seconds(1))
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(results.failures, 1);
+    }
+}
@@ -0,0 +1,50 @@
+//! Helpers for the repositories that make their own ids and timestamps rather than leaving it
+//! to Mongo.
+
+use chrono::{DateTime, TimeZone, Utc};
+
+/// A random id shaped like the ones Mongo hands out, so either store accepts the other's ids.
+pub(super) fn new_id() -> String {
+    format!("{:024x}", rand::random::<u128>() >> 32)
+}
+
+/// The canonical form of `id`, or `None` when it isn't one Mongo would accept either.
+pub(super) fn parse_id(id: &str) -> Option<String> {
+    if id.len() == 24 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(id.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// The current time, truncated to the milliseconds Mongo keeps.
+pub(super) fn now() -> DateTime<Utc> {
+    Utc.timestamp_millis(Utc::now().timestamp_millis())
+}
+
+/// A fresh SQLite database with every migration run, for testing the SQL repositories.
+#[cfg(all(test, feature = "sql"))]
+pub(super) async fn test_pool() -> sqlx::AnyPool {
+    // Every connection to `sqlite::memory:` gets a database of its own
+    let pool = sqlx::any::AnyPoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!().run(&pool).await.unwrap();
+
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_accept_new_ids() {
+        let id = new_id();
+
+        assert_eq!(parse_id(&id), Some(id));
+        assert_eq!(parse_id("not an id"), None);
+    }
+}
@@ -1,95 +1,295 @@
-use std::time::SystemTime;
-
 use async_trait::async_trait;
-use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use chrono::{TimeZone, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document, Regex},
+    options::{Collation, CollationStrength, FindOneOptions, FindOptions, IndexOptions},
+    Collection, Database, IndexModel,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::domain::user::entities::User;
+use crate::{
+    domain::user::entities::{Role, TwoFactor, User},
+    repositories::error::{DatabaseError, DatabaseErrorKind},
+};
 
 use super::{
-    CreateError, CreateInput, FindByIdError, FindOneByEmailError, MongoRepository, Repository,
+    role_from_name, role_name, ConsumeRecoveryCodeError, ConsumeTotpStepError, CreateError,
+    CreateInput, Cursor, DeleteError, FindByIdError, FindOneByEmailError, ListError, ListFilter,
+    ListInput, ListOrder, MarkVerifiedError, Repository, SetDisabledError, SetRolesError,
+    SetTwoFactorError, UpdatePasswordError,
 };
 
+pub struct MongoRepository {
+    collection: Collection<UserDocument>,
+    error: bool,
+}
+
+impl MongoRepository {
+    pub fn new(db: Database, collection: &str) -> Self {
+        Self {
+            error: false,
+            collection: db.collection(collection),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 struct UserDocument {
     _id: ObjectId,
     email: String,
     password: String,
     created_at: DateTime,
+    // Users from before this was tracked fall back to `created_at`
+    #[serde(default)]
+    updated_at: Option<DateTime>,
+    #[serde(default)]
+    verified: bool,
+    #[serde(default)]
+    verified_at: Option<DateTime>,
+    // Users from before roles existed are plain users
+    #[serde(default = "default_roles")]
+    roles: Vec<String>,
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    two_factor: TwoFactorDocument,
+}
+
+fn default_roles() -> Vec<String> {
+    vec![role_name(Role::User).to_string()]
+}
+
+fn role_names(roles: &[Role]) -> Vec<&'static str> {
+    roles.iter().map(|role| role_name(*role)).collect()
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct TwoFactorDocument {
+    pending_secret: Option<String>,
+    secret: Option<String>,
+    #[serde(default)]
+    recovery_code_hashes: Vec<String>,
+    last_totp_step: Option<i64>,
+}
+
+impl From<TwoFactorDocument> for TwoFactor {
+    fn from(doc: TwoFactorDocument) -> Self {
+        Self {
+            pending_secret: doc.pending_secret,
+            secret: doc.secret,
+            recovery_code_hashes: doc.recovery_code_hashes,
+            last_totp_step: doc.last_totp_step,
+        }
+    }
+}
+
+impl From<UserDocument> for User {
+    fn from(doc: UserDocument) -> Self {
+        Self {
+            id: doc._id.to_hex(),
+            email: doc.email,
+            password: doc.password,
+            verified_at: match (doc.verified, doc.verified_at) {
+                (true, Some(verified_at)) => {
+                    Some(Utc.timestamp_millis(verified_at.timestamp_millis()))
+                }
+                _ => None,
+            },
+            roles: doc
+                .roles
+                .iter()
+                .filter_map(|name| role_from_name(name))
+                .collect(),
+            disabled: doc.disabled,
+            created_at: Utc.timestamp_millis(doc.created_at.timestamp_millis()),
+            updated_at: Utc
+                .timestamp_millis(doc.updated_at.unwrap_or(doc.created_at).timestamp_millis()),
+            two_factor: doc.two_factor.into(),
+        }
+    }
+}
+
+/// Compares emails ignoring case, so addresses stored before they were normalized still clash
+/// with and match their lowercase form.
+fn email_collation() -> Collation {
+    Collation::builder()
+        .locale("en")
+        .strength(CollationStrength::Secondary)
+        .build()
+}
+
+/// Creates the indexes the repository relies on, most importantly the one that makes emails
+/// unique. Does nothing for indexes that already exist.
+pub async fn ensure_indexes(database: &Database, collection: &str) -> mongodb::error::Result<()> {
+    let email_index = IndexModel::builder()
+        .keys(doc! { "email": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("email_unique".to_string())
+                .unique(true)
+                .collation(email_collation())
+                .build(),
+        )
+        .build();
+
+    database
+        .collection::<Document>(collection)
+        .create_index(email_index, None)
+        .await?;
+
+    Ok(())
+}
+
+fn to_bson_date(date: chrono::DateTime<Utc>) -> DateTime {
+    DateTime::from_millis(date.timestamp_millis())
+}
+
+/// Escapes regex metacharacters so user input only ever matches literally.
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}-/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn filter_conditions(filter: &ListFilter) -> Vec<Document> {
+    let mut conditions = Vec::new();
+
+    if let Some(email) = &filter.email_contains {
+        conditions.push(doc! {
+            "email": Regex { pattern: escape_regex(email), options: "i".to_string() }
+        });
+    }
+
+    let mut created_at = Document::new();
+    if let Some(after) = filter.created_after {
+        created_at.insert("$gt", to_bson_date(after));
+    }
+    if let Some(before) = filter.created_before {
+        created_at.insert("$lt", to_bson_date(before));
+    }
+    if !created_at.is_empty() {
+        conditions.push(doc! { "created_at": created_at });
+    }
+
+    match filter.role {
+        // Users from before roles existed have no roles field but count as plain users
+        Some(Role::User) => conditions.push(doc! {
+            "$or": [{ "roles": role_name(Role::User) }, { "roles": { "$exists": false } }]
+        }),
+        Some(role) => conditions.push(doc! { "roles": role_name(role) }),
+        None => {}
+    }
+
+    conditions
+}
+
+/// Matches users on the `$gt` or `$lt` side of `cursor`, breaking creation time ties by id.
+fn beyond(cursor: &Cursor, operator: &str) -> Result<Document, ListError> {
+    let id = ObjectId::parse_str(&cursor.id).map_err(|_| ListError::InvalidCursor)?;
+    let created_at = to_bson_date(cursor.created_at);
+
+    Ok(doc! {
+        "$or": [
+            { "created_at": { operator: created_at } },
+            { "created_at": created_at, "_id": { operator: id } },
+        ]
+    })
+}
+
+fn list_query(input: &ListInput) -> Result<Document, ListError> {
+    let (after, before) = match input.order {
+        ListOrder::CreatedAtAsc => ("$gt", "$lt"),
+        ListOrder::CreatedAtDesc => ("$lt", "$gt"),
+    };
+
+    let mut conditions = filter_conditions(&input.filter);
+    if let Some(cursor) = &input.after {
+        conditions.push(beyond(cursor, after)?);
+    }
+    if let Some(cursor) = &input.before {
+        conditions.push(beyond(cursor, before)?);
+    }
+
+    if conditions.is_empty() {
+        Ok(Document::new())
+    } else {
+        Ok(doc! { "$and": conditions })
+    }
 }
 
 #[async_trait]
 impl Repository for MongoRepository {
     async fn find_by_id(&self, id: String) -> Result<User, FindByIdError> {
         if self.error {
-            return Err(FindByIdError::Unknown);
+            return Err(FindByIdError::Database(DatabaseError::simulated()));
         }
 
-        let unlocked_database = self.database.lock().await;
-
         let id = match ObjectId::parse_str(id) {
             Ok(id) => id,
             Err(_) => return Err(FindByIdError::InvalidId),
         };
 
-        let results = unlocked_database
-            .collection::<UserDocument>(self.collection.as_str())
+        let results = self
+            .collection
             .find_one(Some(doc! { "_id": id }), None)
             .await;
 
         match results {
-            Ok(Some(doc)) => Ok(User {
-                id: doc._id.to_hex(),
-                email: doc.email,
-                password: doc.password,
-            }),
+            Ok(Some(doc)) => Ok(doc.into()),
             Ok(None) => Err(FindByIdError::NotFound),
-            Err(err) => {
-                println!("Error In find_by_id: {:?}", err);
-                Err(FindByIdError::Unknown)
-            }
+            Err(err) => Err(FindByIdError::Database(err.into())),
         }
     }
 
     async fn find_one_by_email(&self, email: String) -> Result<Option<User>, FindOneByEmailError> {
         if self.error {
-            return Err(FindOneByEmailError::Unknown);
+            return Err(FindOneByEmailError::Database(DatabaseError::simulated()));
         }
 
-        let unlocked_database = self.database.lock().await;
-
-        let results = unlocked_database
-            .collection::<UserDocument>(self.collection.as_str())
-            .find_one(Some(doc! { "email": email }), None)
+        let results = self
+            .collection
+            .find_one(
+                Some(doc! { "email": email }),
+                FindOneOptions::builder()
+                    .collation(email_collation())
+                    .build(),
+            )
             .await;
 
         match results {
-            Ok(Some(doc)) => Ok(Some(User {
-                id: doc._id.to_hex(),
-                email: doc.email,
-                password: doc.password,
-            })),
+            Ok(Some(doc)) => Ok(Some(doc.into())),
             Ok(None) => Ok(None),
-            Err(_) => Err(FindOneByEmailError::Unknown),
+            Err(err) => Err(FindOneByEmailError::Database(err.into())),
         }
     }
 
     async fn create(&self, input: CreateInput) -> Result<User, CreateError> {
         if self.error {
-            return Err(CreateError::Unknown);
+            return Err(CreateError::Database(DatabaseError::simulated()));
         }
 
-        let unlocked_database = self.database.lock().await;
-        let now = SystemTime::now();
+        let now = DateTime::now();
 
         let new_doc = doc! {
             "email": input.email.clone(),
             "password": input.password.clone(),
-            "created_at": DateTime::from_system_time(now)
+            "created_at": now,
+            "updated_at": now,
+            "verified": false,
+            "roles": role_names(&input.roles),
+            "disabled": false,
         };
 
-        let results = unlocked_database
-            .collection(self.collection.as_str())
+        let results = self
+            .collection
+            .clone_with_type::<Document>()
             .insert_one(new_doc, None)
             .await;
 
@@ -98,8 +298,307 @@ impl Repository for MongoRepository {
                 id: insert_result.inserted_id.as_object_id().unwrap().to_hex(),
                 email: input.email,
                 password: input.password,
+                verified_at: None,
+                roles: input.roles,
+                disabled: false,
+                created_at: Utc.timestamp_millis(now.timestamp_millis()),
+                updated_at: Utc.timestamp_millis(now.timestamp_millis()),
+                two_factor: TwoFactor::default(),
             }),
-            Err(_) => Err(CreateError::Unknown),
+            Err(err) => match DatabaseError::from(err) {
+                err if err.kind() == DatabaseErrorKind::DuplicateKey => {
+                    Err(CreateError::AlreadyExists)
+                }
+                err => Err(CreateError::Database(err)),
+            },
+        }
+    }
+
+    async fn update_password(
+        &self,
+        id: String,
+        password: String,
+    ) -> Result<(), UpdatePasswordError> {
+        if self.error {
+            return Err(UpdatePasswordError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(UpdatePasswordError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": { "password": password },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) if update_result.matched_count == 0 => {
+                Err(UpdatePasswordError::NotFound)
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(UpdatePasswordError::Database(err.into())),
+        }
+    }
+
+    async fn mark_verified(&self, id: String) -> Result<(), MarkVerifiedError> {
+        if self.error {
+            return Err(MarkVerifiedError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(MarkVerifiedError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": { "verified": true, "verified_at": DateTime::now() },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) if update_result.matched_count == 0 => {
+                Err(MarkVerifiedError::NotFound)
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(MarkVerifiedError::Database(err.into())),
+        }
+    }
+
+    async fn set_two_factor(
+        &self,
+        id: String,
+        two_factor: TwoFactor,
+    ) -> Result<(), SetTwoFactorError> {
+        if self.error {
+            return Err(SetTwoFactorError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(SetTwoFactorError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": { "two_factor": {
+                        "pending_secret": two_factor.pending_secret,
+                        "secret": two_factor.secret,
+                        "recovery_code_hashes": two_factor.recovery_code_hashes,
+                        "last_totp_step": two_factor.last_totp_step,
+                    } },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) if update_result.matched_count == 0 => {
+                Err(SetTwoFactorError::NotFound)
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(SetTwoFactorError::Database(err.into())),
+        }
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        id: String,
+        code_hash: String,
+    ) -> Result<bool, ConsumeRecoveryCodeError> {
+        if self.error {
+            return Err(ConsumeRecoveryCodeError::Database(
+                DatabaseError::simulated(),
+            ));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(ConsumeRecoveryCodeError::InvalidId),
+        };
+
+        // Matching on the hash makes the pull conditional, so concurrent uses can't both succeed
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id, "two_factor.recovery_code_hashes": &code_hash },
+                doc! {
+                    "$pull": { "two_factor.recovery_code_hashes": &code_hash },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) => Ok(update_result.modified_count == 1),
+            Err(err) => Err(ConsumeRecoveryCodeError::Database(err.into())),
+        }
+    }
+
+    async fn consume_totp_step(&self, id: String, step: i64) -> Result<bool, ConsumeTotpStepError> {
+        if self.error {
+            return Err(ConsumeTotpStepError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(ConsumeTotpStepError::InvalidId),
+        };
+
+        // Only matching earlier steps makes the update conditional, so a code can't be used twice
+        let results = self
+            .collection
+            .update_one(
+                doc! {
+                    "_id": id,
+                    "$or": [
+                        { "two_factor.last_totp_step": null },
+                        { "two_factor.last_totp_step": { "$lt": step } },
+                    ],
+                },
+                doc! {
+                    "$set": { "two_factor.last_totp_step": step },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) => Ok(update_result.modified_count == 1),
+            Err(err) => Err(ConsumeTotpStepError::Database(err.into())),
+        }
+    }
+
+    async fn set_roles(&self, id: String, roles: Vec<Role>) -> Result<(), SetRolesError> {
+        if self.error {
+            return Err(SetRolesError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(SetRolesError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": { "roles": role_names(&roles) },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) if update_result.matched_count == 0 => Err(SetRolesError::NotFound),
+            Ok(_) => Ok(()),
+            Err(err) => Err(SetRolesError::Database(err.into())),
+        }
+    }
+
+    async fn set_disabled(&self, id: String, disabled: bool) -> Result<(), SetDisabledError> {
+        if self.error {
+            return Err(SetDisabledError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(SetDisabledError::InvalidId),
+        };
+
+        let results = self
+            .collection
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": { "disabled": disabled },
+                    "$currentDate": { "updated_at": true },
+                },
+                None,
+            )
+            .await;
+
+        match results {
+            Ok(update_result) if update_result.matched_count == 0 => {
+                Err(SetDisabledError::NotFound)
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(SetDisabledError::Database(err.into())),
+        }
+    }
+
+    async fn delete(&self, id: String) -> Result<(), DeleteError> {
+        if self.error {
+            return Err(DeleteError::Database(DatabaseError::simulated()));
+        }
+
+        let id = match ObjectId::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Err(DeleteError::InvalidId),
+        };
+
+        let results = self.collection.delete_one(doc! { "_id": id }, None).await;
+
+        match results {
+            Ok(delete_result) if delete_result.deleted_count == 0 => Err(DeleteError::NotFound),
+            Ok(_) => Ok(()),
+            Err(err) => Err(DeleteError::Database(err.into())),
+        }
+    }
+
+    async fn list(&self, input: ListInput) -> Result<Vec<(Cursor, User)>, ListError> {
+        if self.error {
+            return Err(ListError::Database(DatabaseError::simulated()));
+        }
+
+        let query = list_query(&input)?;
+        let ascending = (input.order == ListOrder::CreatedAtAsc) != input.reverse;
+        let direction = if ascending { 1 } else { -1 };
+        let options = FindOptions::builder()
+            .sort(doc! { "created_at": direction, "_id": direction })
+            .limit(input.limit as i64)
+            .build();
+
+        let results = match self.collection.find(query, options).await {
+            Ok(cursor) => cursor.try_collect::<Vec<_>>().await,
+            Err(err) => Err(err),
+        };
+
+        match results {
+            Ok(docs) => Ok(docs
+                .into_iter()
+                .map(|doc| {
+                    let cursor = Cursor {
+                        created_at: Utc.timestamp_millis(doc.created_at.timestamp_millis()),
+                        id: doc._id.to_hex(),
+                    };
+                    (cursor, doc.into())
+                })
+                .collect()),
+            Err(err) => Err(ListError::Database(err.into())),
         }
     }
 }
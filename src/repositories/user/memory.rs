@@ -0,0 +1,381 @@
+use std::{cmp::Ordering, collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::{
+    domain::user::entities::{Role, TwoFactor, User},
+    repositories::support::{new_id, now, parse_id},
+};
+
+use super::{
+    ConsumeRecoveryCodeError, ConsumeTotpStepError, CreateError, CreateInput, Cursor, DeleteError,
+    FindByIdError, FindOneByEmailError, ListError, ListFilter, ListInput, ListOrder,
+    MarkVerifiedError, Repository, SetDisabledError, SetRolesError, SetTwoFactorError,
+    UpdatePasswordError,
+};
+
+/// Keeps users in this process, so they are gone once it stops.
+pub struct InMemoryRepository {
+    users: Mutex<HashMap<String, User>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self {
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Applies `update` to the user with `id`, returning whether there was one.
+    fn update(&self, id: &str, update: impl FnOnce(&mut User)) -> bool {
+        let mut users = self.users.lock().unwrap();
+
+        match users.get_mut(id) {
+            Some(user) => {
+                update(user);
+                user.updated_at = now();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Same as the case-insensitive collation the Mongo email index uses.
+fn same_email(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+fn matches_filter(filter: &ListFilter, user: &User) -> bool {
+    if let Some(email) = &filter.email_contains {
+        if !user.email.to_lowercase().contains(&email.to_lowercase()) {
+            return false;
+        }
+    }
+    if let Some(after) = filter.created_after {
+        if user.created_at <= after {
+            return false;
+        }
+    }
+    if let Some(before) = filter.created_before {
+        if user.created_at >= before {
+            return false;
+        }
+    }
+    if let Some(role) = filter.role {
+        if !user.roles.contains(&role) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn sort_key(user: &User) -> (i64, &str) {
+    (user.created_at.timestamp_millis(), user.id.as_str())
+}
+
+fn cursor_key(cursor: &Cursor) -> Result<(i64, String), ListError> {
+    let id = parse_id(&cursor.id).ok_or(ListError::InvalidCursor)?;
+
+    Ok((cursor.created_at.timestamp_millis(), id))
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn find_by_id(&self, id: String) -> Result<User, FindByIdError> {
+        let id = parse_id(&id).ok_or(FindByIdError::InvalidId)?;
+
+        let users = self.users.lock().unwrap();
+
+        users.get(&id).cloned().ok_or(FindByIdError::NotFound)
+    }
+
+    async fn find_one_by_email(&self, email: String) -> Result<Option<User>, FindOneByEmailError> {
+        let users = self.users.lock().unwrap();
+
+        Ok(users
+            .values()
+            .find(|user| same_email(&user.email, &email))
+            .cloned())
+    }
+
+    async fn create(&self, input: CreateInput) -> Result<User, CreateError> {
+        let mut users = self.users.lock().unwrap();
+
+        if users
+            .values()
+            .any(|user| same_email(&user.email, &input.email))
+        {
+            return Err(CreateError::AlreadyExists);
+        }
+
+        let now = now();
+        let user = User {
+            id: new_id(),
+            email: input.email,
+            password: input.password,
+            verified_at: None,
+            roles: input.roles,
+            disabled: false,
+            created_at: now,
+            updated_at: now,
+            two_factor: TwoFactor::default(),
+        };
+        users.insert(user.id.clone(), user.clone());
+
+        Ok(user)
+    }
+
+    async fn update_password(
+        &self,
+        id: String,
+        password: String,
+    ) -> Result<(), UpdatePasswordError> {
+        let id = parse_id(&id).ok_or(UpdatePasswordError::InvalidId)?;
+
+        if self.update(&id, |user| user.password = password) {
+            Ok(())
+        } else {
+            Err(UpdatePasswordError::NotFound)
+        }
+    }
+
+    async fn mark_verified(&self, id: String) -> Result<(), MarkVerifiedError> {
+        let id = parse_id(&id).ok_or(MarkVerifiedError::InvalidId)?;
+
+        if self.update(&id, |user| user.verified_at = Some(now())) {
+            Ok(())
+        } else {
+            Err(MarkVerifiedError::NotFound)
+        }
+    }
+
+    async fn set_two_factor(
+        &self,
+        id: String,
+        two_factor: TwoFactor,
+    ) -> Result<(), SetTwoFactorError> {
+        let id = parse_id(&id).ok_or(SetTwoFactorError::InvalidId)?;
+
+        if self.update(&id, |user| user.two_factor = two_factor) {
+            Ok(())
+        } else {
+            Err(SetTwoFactorError::NotFound)
+        }
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        id: String,
+        code_hash: String,
+    ) -> Result<bool, ConsumeRecoveryCodeError> {
+        let id = parse_id(&id).ok_or(ConsumeRecoveryCodeError::InvalidId)?;
+
+        let mut users = self.users.lock().unwrap();
+
+        let user = match users.get_mut(&id) {
+            Some(user) => user,
+            None => return Ok(false),
+        };
+        let hashes = &mut user.two_factor.recovery_code_hashes;
+        match hashes.iter().position(|hash| *hash == code_hash) {
+            Some(position) => {
+                hashes.remove(position);
+                user.updated_at = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn consume_totp_step(&self, id: String, step: i64) -> Result<bool, ConsumeTotpStepError> {
+        let id = parse_id(&id).ok_or(ConsumeTotpStepError::InvalidId)?;
+
+        let mut users = self.users.lock().unwrap();
+
+        match users.get_mut(&id) {
+            Some(user)
+                if user
+                    .two_factor
+                    .last_totp_step
+                    .is_none_or(|last| last < step) =>
+            {
+                user.two_factor.last_totp_step = Some(step);
+                user.updated_at = now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_roles(&self, id: String, roles: Vec<Role>) -> Result<(), SetRolesError> {
+        let id = parse_id(&id).ok_or(SetRolesError::InvalidId)?;
+
+        if self.update(&id, |user| user.roles = roles) {
+            Ok(())
+        } else {
+            Err(SetRolesError::NotFound)
+        }
+    }
+
+    async fn set_disabled(&self, id: String, disabled: bool) -> Result<(), SetDisabledError> {
+        let id = parse_id(&id).ok_or(SetDisabledError::InvalidId)?;
+
+        if self.update(&id, |user| user.disabled = disabled) {
+            Ok(())
+        } else {
+            Err(SetDisabledError::NotFound)
+        }
+    }
+
+    async fn delete(&self, id: String) -> Result<(), DeleteError> {
+        let id = parse_id(&id).ok_or(DeleteError::InvalidId)?;
+
+        let mut users = self.users.lock().unwrap();
+
+        match users.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(DeleteError::NotFound),
+        }
+    }
+
+    async fn list(&self, input: ListInput) -> Result<Vec<(Cursor, User)>, ListError> {
+        let after = input.after.as_ref().map(cursor_key).transpose()?;
+        let before = input.before.as_ref().map(cursor_key).transpose()?;
+
+        let users = self.users.lock().unwrap();
+
+        let mut matching = users
+            .values()
+            .filter(|user| matches_filter(&input.filter, user))
+            .collect::<Vec<_>>();
+        matching.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+        if input.order == ListOrder::CreatedAtDesc {
+            matching.reverse();
+        }
+
+        let (after_side, before_side) = match input.order {
+            ListOrder::CreatedAtAsc => (Ordering::Greater, Ordering::Less),
+            ListOrder::CreatedAtDesc => (Ordering::Less, Ordering::Greater),
+        };
+        matching.retain(|user| {
+            let side = |cursor: &(i64, String)| sort_key(user).cmp(&(cursor.0, cursor.1.as_str()));
+
+            after
+                .as_ref()
+                .is_none_or(|cursor| side(cursor) == after_side)
+                && before
+                    .as_ref()
+                    .is_none_or(|cursor| side(cursor) == before_side)
+        });
+
+        if input.reverse {
+            matching.reverse();
+        }
+
+        Ok(matching
+            .into_iter()
+            .take(input.limit)
+            .map(|user| {
+                let cursor = Cursor {
+                    created_at: user.created_at,
+                    id: user.id.clone(),
+                };
+                (cursor, user.clone())
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_input(email: &str) -> CreateInput {
+        CreateInput {
+            email: email.to_string(),
+            password: "hash".to_string(),
+            roles: vec![Role::User],
+        }
+    }
+
+    fn list_input(order: ListOrder) -> ListInput {
+        ListInput {
+            filter: ListFilter::default(),
+            order,
+            after: None,
+            before: None,
+            reverse: false,
+            limit: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_emails_differing_only_in_case() {
+        let repo = InMemoryRepository::new();
+        repo.create(create_input("someone@example.com"))
+            .await
+            .unwrap();
+
+        let results = repo.create(create_input("Someone@Example.com")).await;
+
+        assert!(matches!(results, Err(CreateError::AlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn should_find_created_user_by_id_and_email() {
+        let repo = InMemoryRepository::new();
+        let user = repo
+            .create(create_input("someone@example.com"))
+            .await
+            .unwrap();
+
+        assert_eq!(repo.find_by_id(user.id.clone()).await.unwrap(), user);
+        assert_eq!(
+            repo.find_one_by_email("SOMEONE@example.com".to_string())
+                .await
+                .unwrap(),
+            Some(user)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_invalid_id_error_for_malformed_ids() {
+        let repo = InMemoryRepository::new();
+
+        let results = repo.find_by_id("id".to_string()).await;
+
+        assert!(matches!(results, Err(FindByIdError::InvalidId)));
+    }
+
+    #[tokio::test]
+    async fn should_list_users_after_cursor_in_order() {
+        let repo = InMemoryRepository::new();
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            repo.create(create_input(email)).await.unwrap();
+        }
+        let ids = |page: &[(Cursor, User)]| {
+            page.iter()
+                .map(|(_, user)| user.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let all = repo
+            .list(list_input(ListOrder::CreatedAtDesc))
+            .await
+            .ok()
+            .unwrap();
+        let rest = repo
+            .list(ListInput {
+                after: Some(all[0].0.clone()),
+                ..list_input(ListOrder::CreatedAtDesc)
+            })
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(all.len(), 3);
+        assert_eq!(ids(&rest), ids(&all[1..]),);
+    }
+}
@@ -1,53 +1,432 @@
+#[cfg(feature = "mongo")]
 pub mod adapter;
+mod memory;
+#[cfg(feature = "sql")]
+mod sql;
+
+#[cfg(feature = "mongo")]
+pub use adapter::MongoRepository;
+pub use memory::InMemoryRepository;
+#[cfg(feature = "sql")]
+pub use sql::SqlRepository;
+
+use std::{error::Error, fmt};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 #[cfg(test)]
 use mockall::*;
-use mongodb::Database;
-use tokio::sync::Mutex;
 
-use crate::domain::user::entities::User;
+use crate::{
+    domain::user::entities::{Role, TwoFactor, User},
+    repositories::error::DatabaseError,
+};
 
 pub struct CreateInput {
     pub email: String,
     pub password: String,
+    pub roles: Vec<Role>,
+}
+
+/// Position of a user within a listing, ordered by creation time and then id.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: String,
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct ListFilter {
+    pub email_contains: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub role: Option<Role>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ListOrder {
+    CreatedAtAsc,
+    CreatedAtDesc,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ListInput {
+    pub filter: ListFilter,
+    pub order: ListOrder,
+    /// Only users strictly after this position in `order`
+    pub after: Option<Cursor>,
+    /// Only users strictly before this position in `order`
+    pub before: Option<Cursor>,
+    /// Walk `order` from the end, so `limit` keeps the last users rather than the first. Users
+    /// still come back in the order they were walked.
+    pub reverse: bool,
+    pub limit: usize,
 }
 
+#[derive(Debug)]
 pub enum CreateError {
-    Unknown,
+    /// Another user already has the email
+    AlreadyExists,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum FindByIdError {
     InvalidId,
     NotFound,
-    Unknown,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum FindOneByEmailError {
-    Unknown,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+impl fmt::Display for CreateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateError::AlreadyExists => f.write_str("email already in use"),
+            CreateError::Database(err) => write!(f, "could not create user: {}", err),
+        }
+    }
+}
+
+impl Error for CreateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CreateError::AlreadyExists => None,
+            CreateError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for FindByIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindByIdError::InvalidId => f.write_str("invalid user id"),
+            FindByIdError::NotFound => f.write_str("user not found"),
+            FindByIdError::Database(err) => write!(f, "could not find user: {}", err),
+        }
+    }
+}
+
+impl Error for FindByIdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FindByIdError::InvalidId | FindByIdError::NotFound => None,
+            FindByIdError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for FindOneByEmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindOneByEmailError::Database(err) => write!(f, "could not find user: {}", err),
+        }
+    }
+}
+
+impl Error for FindOneByEmailError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FindOneByEmailError::Database(err) => Some(err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UpdatePasswordError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum MarkVerifiedError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum SetTwoFactorError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum ConsumeRecoveryCodeError {
+    InvalidId,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum ConsumeTotpStepError {
+    InvalidId,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum SetRolesError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum SetDisabledError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum DeleteError {
+    InvalidId,
+    NotFound,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum ListError {
+    InvalidCursor,
+    #[cfg_attr(not(any(feature = "mongo", feature = "sql")), allow(dead_code))]
+    Database(DatabaseError),
+}
+
+impl fmt::Display for UpdatePasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdatePasswordError::InvalidId => f.write_str("invalid user id"),
+            UpdatePasswordError::NotFound => f.write_str("user not found"),
+            UpdatePasswordError::Database(err) => write!(f, "could not update password: {}", err),
+        }
+    }
+}
+
+impl Error for UpdatePasswordError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UpdatePasswordError::InvalidId | UpdatePasswordError::NotFound => None,
+            UpdatePasswordError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for MarkVerifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkVerifiedError::InvalidId => f.write_str("invalid user id"),
+            MarkVerifiedError::NotFound => f.write_str("user not found"),
+            MarkVerifiedError::Database(err) => write!(f, "could not mark user verified: {}", err),
+        }
+    }
 }
 
-pub struct MongoRepository {
-    database: Mutex<Database>,
-    collection: String,
-    error: bool,
+impl Error for MarkVerifiedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MarkVerifiedError::InvalidId | MarkVerifiedError::NotFound => None,
+            MarkVerifiedError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for SetTwoFactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetTwoFactorError::InvalidId => f.write_str("invalid user id"),
+            SetTwoFactorError::NotFound => f.write_str("user not found"),
+            SetTwoFactorError::Database(err) => {
+                write!(f, "could not update two factor settings: {}", err)
+            }
+        }
+    }
+}
+
+impl Error for SetTwoFactorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SetTwoFactorError::InvalidId | SetTwoFactorError::NotFound => None,
+            SetTwoFactorError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for ConsumeRecoveryCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsumeRecoveryCodeError::InvalidId => f.write_str("invalid user id"),
+            ConsumeRecoveryCodeError::Database(err) => {
+                write!(f, "could not consume recovery code: {}", err)
+            }
+        }
+    }
+}
+
+impl Error for ConsumeRecoveryCodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConsumeRecoveryCodeError::InvalidId => None,
+            ConsumeRecoveryCodeError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for ConsumeTotpStepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsumeTotpStepError::InvalidId => f.write_str("invalid user id"),
+            ConsumeTotpStepError::Database(err) => {
+                write!(f, "could not consume totp step: {}", err)
+            }
+        }
+    }
+}
+
+impl Error for ConsumeTotpStepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConsumeTotpStepError::InvalidId => None,
+            ConsumeTotpStepError::Database(err) => Some(err),
+        }
+    }
 }
 
-impl MongoRepository {
-    pub fn new(db: Database) -> Self {
-        Self {
-            error: false,
-            database: Mutex::new(db),
-            collection: "users".to_string(),
+impl fmt::Display for SetRolesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetRolesError::InvalidId => f.write_str("invalid user id"),
+            SetRolesError::NotFound => f.write_str("user not found"),
+            SetRolesError::Database(err) => write!(f, "could not set roles: {}", err),
         }
     }
 }
 
+impl Error for SetRolesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SetRolesError::InvalidId | SetRolesError::NotFound => None,
+            SetRolesError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for SetDisabledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetDisabledError::InvalidId => f.write_str("invalid user id"),
+            SetDisabledError::NotFound => f.write_str("user not found"),
+            SetDisabledError::Database(err) => write!(f, "could not update disabled flag: {}", err),
+        }
+    }
+}
+
+impl Error for SetDisabledError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SetDisabledError::InvalidId | SetDisabledError::NotFound => None,
+            SetDisabledError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteError::InvalidId => f.write_str("invalid user id"),
+            DeleteError::NotFound => f.write_str("user not found"),
+            DeleteError::Database(err) => write!(f, "could not delete user: {}", err),
+        }
+    }
+}
+
+impl Error for DeleteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeleteError::InvalidId | DeleteError::NotFound => None,
+            DeleteError::Database(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for ListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListError::InvalidCursor => f.write_str("invalid cursor"),
+            ListError::Database(err) => write!(f, "could not list users: {}", err),
+        }
+    }
+}
+
+impl Error for ListError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ListError::InvalidCursor => None,
+            ListError::Database(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(any(feature = "mongo", feature = "sql"))]
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Admin => "admin",
+    }
+}
+
+#[cfg(any(feature = "mongo", feature = "sql"))]
+fn role_from_name(name: &str) -> Option<Role> {
+    match name {
+        "user" => Some(Role::User),
+        "admin" => Some(Role::Admin),
+        _ => None,
+    }
+}
+
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait Repository: Send + Sync {
     async fn find_by_id(&self, id: String) -> Result<User, FindByIdError>;
     async fn find_one_by_email(&self, email: String) -> Result<Option<User>, FindOneByEmailError>;
     async fn create(&self, input: CreateInput) -> Result<User, CreateError>;
+    async fn update_password(
+        &self,
+        id: String,
+        password: String,
+    ) -> Result<(), UpdatePasswordError>;
+    async fn mark_verified(&self, id: String) -> Result<(), MarkVerifiedError>;
+    async fn set_two_factor(
+        &self,
+        id: String,
+        two_factor: TwoFactor,
+    ) -> Result<(), SetTwoFactorError>;
+    /// Atomically removes an unused recovery code, returning whether the user had it.
+    async fn consume_recovery_code(
+        &self,
+        id: String,
+        code_hash: String,
+    ) -> Result<bool, ConsumeRecoveryCodeError>;
+    /// Atomically records `step` as the last one a TOTP code was accepted for, returning false if
+    /// it or a later one already was.
+    async fn consume_totp_step(&self, id: String, step: i64) -> Result<bool, ConsumeTotpStepError>;
+    async fn set_roles(&self, id: String, roles: Vec<Role>) -> Result<(), SetRolesError>;
+    async fn set_disabled(&self, id: String, disabled: bool) -> Result<(), SetDisabledError>;
+    async fn delete(&self, id: String) -> Result<(), DeleteError>;
+    async fn list(&self, input: ListInput) -> Result<Vec<(Cursor, User)>, ListError>;
 }
@@ -0,0 +1,680 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::{
+    domain::user::entities::{Role, TwoFactor, User},
+    repositories::{
+        error::{DatabaseError, DatabaseErrorKind},
+        support::{new_id, now, parse_id},
+    },
+};
+
+use super::{
+    role_from_name, role_name, ConsumeRecoveryCodeError, ConsumeTotpStepError, CreateError,
+    CreateInput, Cursor, DeleteError, FindByIdError, FindOneByEmailError, ListError, ListFilter,
+    ListInput, ListOrder, MarkVerifiedError, Repository, SetDisabledError, SetRolesError,
+    SetTwoFactorError, UpdatePasswordError,
+};
+
+const USER_COLUMNS: &str = "id, email, password, verified_at, roles, disabled, created_at, \
+                            updated_at, totp_pending_secret, totp_secret, totp_last_step";
+
+pub struct SqlRepository {
+    pool: AnyPool,
+}
+
+impl SqlRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+
+    /// Fills in the recovery codes of the users in `rows`, which live in a table of their own.
+    async fn with_recovery_codes(&self, rows: Vec<AnyRow>) -> Result<Vec<User>, sqlx::Error> {
+        let mut users = rows
+            .iter()
+            .map(user_from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        if users.is_empty() {
+            return Ok(users);
+        }
+
+        let placeholders = (1..=users.len())
+            .map(|index| format!("${}", index))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "SELECT user_id, code_hash FROM recovery_codes WHERE user_id IN ({})",
+            placeholders
+        );
+        let mut query = sqlx::query(&sql);
+        for user in &users {
+            query = query.bind(user.id.clone());
+        }
+
+        let mut codes = HashMap::<String, Vec<String>>::new();
+        for row in query.fetch_all(&self.pool).await? {
+            codes
+                .entry(row.try_get("user_id")?)
+                .or_default()
+                .push(row.try_get("code_hash")?);
+        }
+        for user in &mut users {
+            user.two_factor.recovery_code_hashes = codes.remove(&user.id).unwrap_or_default();
+        }
+
+        Ok(users)
+    }
+
+    /// Looks up a single user matching `condition`, which callers only ever pass as a literal.
+    async fn find_one_where(
+        &self,
+        condition: &str,
+        value: String,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!("SELECT {} FROM users WHERE {}", USER_COLUMNS, condition);
+        let row = sqlx::query(&sql)
+            .bind(value)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(self
+            .with_recovery_codes(row.into_iter().collect())
+            .await?
+            .pop())
+    }
+
+    /// Runs an `UPDATE` of the user with `id`, returning whether there was one.
+    async fn update(
+        &self,
+        assignments: &str,
+        id: String,
+        values: Vec<Value>,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            "UPDATE users SET {}, updated_at = ${} WHERE id = ${}",
+            assignments,
+            values.len() + 1,
+            values.len() + 2
+        );
+        let mut query = sqlx::query(&sql);
+        for value in values {
+            query = value.bind(query);
+        }
+        let result = query
+            .bind(now().timestamp_millis())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// A value bound to a query assembled at runtime.
+enum Value {
+    Text(String),
+    Integer(i64),
+    Bool(bool),
+}
+
+type AnyQuery<'q> = sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>;
+
+impl Value {
+    fn bind(self, query: AnyQuery<'_>) -> AnyQuery<'_> {
+        match self {
+            Value::Text(value) => query.bind(value),
+            Value::Integer(value) => query.bind(value),
+            Value::Bool(value) => query.bind(value),
+        }
+    }
+}
+
+fn role_names(roles: &[Role]) -> String {
+    roles
+        .iter()
+        .map(|role| role_name(*role))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn from_millis(millis: i64) -> chrono::DateTime<Utc> {
+    Utc.timestamp_millis(millis)
+}
+
+fn user_from_row(row: &AnyRow) -> Result<User, sqlx::Error> {
+    let roles: String = row.try_get("roles")?;
+
+    Ok(User {
+        id: row.try_get("id")?,
+        email: row.try_get("email")?,
+        password: row.try_get("password")?,
+        verified_at: row
+            .try_get::<Option<i64>, _>("verified_at")?
+            .map(from_millis),
+        roles: roles.split(',').filter_map(role_from_name).collect(),
+        disabled: row.try_get("disabled")?,
+        created_at: from_millis(row.try_get("created_at")?),
+        updated_at: from_millis(row.try_get("updated_at")?),
+        two_factor: TwoFactor {
+            pending_secret: row.try_get("totp_pending_secret")?,
+            secret: row.try_get("totp_secret")?,
+            recovery_code_hashes: Vec::new(),
+            last_totp_step: row.try_get("totp_last_step")?,
+        },
+    })
+}
+
+/// Escapes `LIKE` wildcards so user input only ever matches literally.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\%_".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Conditions of a listing, with the values their placeholders stand for.
+#[derive(Default)]
+struct Conditions {
+    clauses: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Conditions {
+    /// Adds `value` to the query, returning the placeholder to refer to it by.
+    fn value(&mut self, value: Value) -> String {
+        self.values.push(value);
+        format!("${}", self.values.len())
+    }
+
+    fn filter(&mut self, filter: &ListFilter) {
+        if let Some(email) = &filter.email_contains {
+            let pattern = self.value(Value::Text(format!(
+                "%{}%",
+                escape_like(&email.to_lowercase())
+            )));
+            self.clauses
+                .push(format!("LOWER(email) LIKE {} ESCAPE '\\'", pattern));
+        }
+        if let Some(after) = filter.created_after {
+            let after = self.value(Value::Integer(after.timestamp_millis()));
+            self.clauses.push(format!("created_at > {}", after));
+        }
+        if let Some(before) = filter.created_before {
+            let before = self.value(Value::Integer(before.timestamp_millis()));
+            self.clauses.push(format!("created_at < {}", before));
+        }
+        if let Some(role) = filter.role {
+            let pattern = self.value(Value::Text(format!("%,{},%", role_name(role))));
+            self.clauses
+                .push(format!("(',' || roles || ',') LIKE {}", pattern));
+        }
+    }
+
+    /// Matches users on the `>` or `<` side of `cursor`, breaking creation time ties by id.
+    fn beyond(&mut self, cursor: &Cursor, operator: &str) -> Result<(), ListError> {
+        let id = parse_id(&cursor.id).ok_or(ListError::InvalidCursor)?;
+        let created_at = cursor.created_at.timestamp_millis();
+
+        let before_tie = self.value(Value::Integer(created_at));
+        let tie = self.value(Value::Integer(created_at));
+        let id = self.value(Value::Text(id));
+        self.clauses.push(format!(
+            "(created_at {operator} {} OR (created_at = {} AND id {operator} {}))",
+            before_tie,
+            tie,
+            id,
+            operator = operator
+        ));
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Repository for SqlRepository {
+    async fn find_by_id(&self, id: String) -> Result<User, FindByIdError> {
+        let id = parse_id(&id).ok_or(FindByIdError::InvalidId)?;
+
+        match self.find_one_where("id = $1", id).await {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(FindByIdError::NotFound),
+            Err(err) => Err(FindByIdError::Database(err.into())),
+        }
+    }
+
+    async fn find_one_by_email(&self, email: String) -> Result<Option<User>, FindOneByEmailError> {
+        self.find_one_where("LOWER(email) = LOWER($1)", email)
+            .await
+            .map_err(|err| FindOneByEmailError::Database(err.into()))
+    }
+
+    async fn create(&self, input: CreateInput) -> Result<User, CreateError> {
+        let now = now();
+        let user = User {
+            id: new_id(),
+            email: input.email,
+            password: input.password,
+            verified_at: None,
+            roles: input.roles,
+            disabled: false,
+            created_at: now,
+            updated_at: now,
+            two_factor: TwoFactor::default(),
+        };
+
+        let results = sqlx::query(
+            "INSERT INTO users (id, email, password, roles, disabled, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(user.id.clone())
+        .bind(user.email.clone())
+        .bind(user.password.clone())
+        .bind(role_names(&user.roles))
+        .bind(false)
+        .bind(now.timestamp_millis())
+        .bind(now.timestamp_millis())
+        .execute(&self.pool)
+        .await;
+
+        match results {
+            Ok(_) => Ok(user),
+            Err(err) => match DatabaseError::from(err) {
+                err if err.kind() == DatabaseErrorKind::DuplicateKey => {
+                    Err(CreateError::AlreadyExists)
+                }
+                err => Err(CreateError::Database(err)),
+            },
+        }
+    }
+
+    async fn update_password(
+        &self,
+        id: String,
+        password: String,
+    ) -> Result<(), UpdatePasswordError> {
+        let id = parse_id(&id).ok_or(UpdatePasswordError::InvalidId)?;
+
+        match self
+            .update("password = $1", id, vec![Value::Text(password)])
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(UpdatePasswordError::NotFound),
+            Err(err) => Err(UpdatePasswordError::Database(err.into())),
+        }
+    }
+
+    async fn mark_verified(&self, id: String) -> Result<(), MarkVerifiedError> {
+        let id = parse_id(&id).ok_or(MarkVerifiedError::InvalidId)?;
+
+        match self
+            .update(
+                "verified_at = $1",
+                id,
+                vec![Value::Integer(now().timestamp_millis())],
+            )
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(MarkVerifiedError::NotFound),
+            Err(err) => Err(MarkVerifiedError::Database(err.into())),
+        }
+    }
+
+    async fn set_two_factor(
+        &self,
+        id: String,
+        two_factor: TwoFactor,
+    ) -> Result<(), SetTwoFactorError> {
+        let id = parse_id(&id).ok_or(SetTwoFactorError::InvalidId)?;
+
+        let results: Result<bool, sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+
+            let updated = sqlx::query(
+                "UPDATE users SET totp_pending_secret = $1, totp_secret = $2, totp_last_step = $3, \
+                 updated_at = $4 WHERE id = $5",
+            )
+            .bind(two_factor.pending_secret)
+            .bind(two_factor.secret)
+            .bind(two_factor.last_totp_step)
+            .bind(now().timestamp_millis())
+            .bind(id.clone())
+            .execute(&mut transaction)
+            .await?;
+            if updated.rows_affected() == 0 {
+                return Ok(false);
+            }
+
+            sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+                .bind(id.clone())
+                .execute(&mut transaction)
+                .await?;
+            for code_hash in two_factor.recovery_code_hashes {
+                sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+                    .bind(id.clone())
+                    .bind(code_hash)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+
+            transaction.commit().await?;
+            Ok(true)
+        }
+        .await;
+
+        match results {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SetTwoFactorError::NotFound),
+            Err(err) => Err(SetTwoFactorError::Database(err.into())),
+        }
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        id: String,
+        code_hash: String,
+    ) -> Result<bool, ConsumeRecoveryCodeError> {
+        let id = parse_id(&id).ok_or(ConsumeRecoveryCodeError::InvalidId)?;
+
+        // Deleting the row is what claims the code, so concurrent uses can't both succeed
+        let results: Result<bool, sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+
+            let deleted =
+                sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1 AND code_hash = $2")
+                    .bind(id.clone())
+                    .bind(code_hash)
+                    .execute(&mut transaction)
+                    .await?;
+            if deleted.rows_affected() == 0 {
+                return Ok(false);
+            }
+
+            sqlx::query("UPDATE users SET updated_at = $1 WHERE id = $2")
+                .bind(now().timestamp_millis())
+                .bind(id)
+                .execute(&mut transaction)
+                .await?;
+
+            transaction.commit().await?;
+            Ok(true)
+        }
+        .await;
+
+        results.map_err(|err| ConsumeRecoveryCodeError::Database(err.into()))
+    }
+
+    async fn consume_totp_step(&self, id: String, step: i64) -> Result<bool, ConsumeTotpStepError> {
+        let id = parse_id(&id).ok_or(ConsumeTotpStepError::InvalidId)?;
+
+        // Only matching earlier steps makes the update conditional, so a code can't be used twice
+        let results = sqlx::query(
+            "UPDATE users SET totp_last_step = $1, updated_at = $2 \
+             WHERE id = $3 AND (totp_last_step IS NULL OR totp_last_step < $1)",
+        )
+        .bind(step)
+        .bind(now().timestamp_millis())
+        .bind(id)
+        .execute(&self.pool)
+        .await;
+
+        match results {
+            Ok(updated) => Ok(updated.rows_affected() == 1),
+            Err(err) => Err(ConsumeTotpStepError::Database(err.into())),
+        }
+    }
+
+    async fn set_roles(&self, id: String, roles: Vec<Role>) -> Result<(), SetRolesError> {
+        let id = parse_id(&id).ok_or(SetRolesError::InvalidId)?;
+
+        match self
+            .update("roles = $1", id, vec![Value::Text(role_names(&roles))])
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SetRolesError::NotFound),
+            Err(err) => Err(SetRolesError::Database(err.into())),
+        }
+    }
+
+    async fn set_disabled(&self, id: String, disabled: bool) -> Result<(), SetDisabledError> {
+        let id = parse_id(&id).ok_or(SetDisabledError::InvalidId)?;
+
+        match self
+            .update("disabled = $1", id, vec![Value::Bool(disabled)])
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SetDisabledError::NotFound),
+            Err(err) => Err(SetDisabledError::Database(err.into())),
+        }
+    }
+
+    async fn delete(&self, id: String) -> Result<(), DeleteError> {
+        let id = parse_id(&id).ok_or(DeleteError::InvalidId)?;
+
+        let results = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match results {
+            Ok(result) if result.rows_affected() == 0 => Err(DeleteError::NotFound),
+            Ok(_) => Ok(()),
+            Err(err) => Err(DeleteError::Database(err.into())),
+        }
+    }
+
+    async fn list(&self, input: ListInput) -> Result<Vec<(Cursor, User)>, ListError> {
+        let (after, before) = match input.order {
+            ListOrder::CreatedAtAsc => (">", "<"),
+            ListOrder::CreatedAtDesc => ("<", ">"),
+        };
+
+        let mut conditions = Conditions::default();
+        conditions.filter(&input.filter);
+        if let Some(cursor) = &input.after {
+            conditions.beyond(cursor, after)?;
+        }
+        if let Some(cursor) = &input.before {
+            conditions.beyond(cursor, before)?;
+        }
+
+        let ascending = (input.order == ListOrder::CreatedAtAsc) != input.reverse;
+        let direction = if ascending { "ASC" } else { "DESC" };
+        let limit = conditions.value(Value::Integer(input.limit as i64));
+        let filter = if conditions.clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.clauses.join(" AND "))
+        };
+        let sql = format!(
+            "SELECT {} FROM users {} ORDER BY created_at {direction}, id {direction} LIMIT {}",
+            USER_COLUMNS,
+            filter,
+            limit,
+            direction = direction
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in conditions.values {
+            query = value.bind(query);
+        }
+        let results = match query.fetch_all(&self.pool).await {
+            Ok(rows) => self.with_recovery_codes(rows).await,
+            Err(err) => Err(err),
+        };
+
+        match results {
+            Ok(users) => Ok(users
+                .into_iter()
+                .map(|user| {
+                    let cursor = Cursor {
+                        created_at: user.created_at,
+                        id: user.id.clone(),
+                    };
+                    (cursor, user)
+                })
+                .collect()),
+            Err(err) => Err(ListError::Database(err.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::support::test_pool;
+
+    use super::*;
+
+    async fn repository() -> SqlRepository {
+        SqlRepository::new(test_pool().await)
+    }
+
+    fn create_input(email: &str) -> CreateInput {
+        CreateInput {
+            email: email.to_string(),
+            password: "hash".to_string(),
+            roles: vec![Role::User, Role::Admin],
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_emails_differing_only_in_case() {
+        let repo = repository().await;
+        repo.create(create_input("someone@example.com"))
+            .await
+            .unwrap();
+
+        let results = repo.create(create_input("Someone@Example.com")).await;
+
+        assert!(matches!(results, Err(CreateError::AlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn should_read_back_what_was_written() {
+        let repo = repository().await;
+        let user = repo
+            .create(create_input("someone@example.com"))
+            .await
+            .unwrap();
+        let two_factor = TwoFactor {
+            pending_secret: None,
+            secret: Some("secret".to_string()),
+            recovery_code_hashes: vec!["first".to_string(), "second".to_string()],
+            last_totp_step: None,
+        };
+        assert!(repo
+            .set_two_factor(user.id.clone(), two_factor)
+            .await
+            .is_ok());
+        assert_eq!(
+            repo.consume_recovery_code(user.id.clone(), "first".to_string())
+                .await
+                .ok(),
+            Some(true)
+        );
+        assert_eq!(
+            repo.consume_recovery_code(user.id.clone(), "first".to_string())
+                .await
+                .ok(),
+            Some(false)
+        );
+
+        let found = repo
+            .find_one_by_email("SOMEONE@example.com".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(found.id, user.id);
+        assert_eq!(found.roles, vec![Role::User, Role::Admin]);
+        assert_eq!(found.two_factor.secret, Some("secret".to_string()));
+        assert_eq!(
+            found.two_factor.recovery_code_hashes,
+            vec!["second".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error_for_unknown_ids() {
+        let repo = repository().await;
+
+        let unknown = repo.find_by_id(new_id()).await;
+        let invalid = repo.find_by_id("id".to_string()).await;
+
+        assert!(matches!(unknown, Err(FindByIdError::NotFound)));
+        assert!(matches!(invalid, Err(FindByIdError::InvalidId)));
+    }
+
+    #[tokio::test]
+    async fn should_list_filtered_users_after_cursor() {
+        let repo = repository().await;
+        for email in [
+            "a@example.com",
+            "b@example.com",
+            "c@example.com",
+            "d@other.com",
+        ] {
+            repo.create(create_input(email)).await.unwrap();
+        }
+        let input = ListInput {
+            filter: ListFilter {
+                email_contains: Some("@EXAMPLE".to_string()),
+                role: Some(Role::Admin),
+                ..ListFilter::default()
+            },
+            order: ListOrder::CreatedAtDesc,
+            after: None,
+            before: None,
+            reverse: false,
+            limit: 10,
+        };
+        let emails = |page: &[(Cursor, User)]| {
+            page.iter()
+                .map(|(_, user)| user.email.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let all = repo.list(input.clone()).await.ok().unwrap();
+        let rest = repo
+            .list(ListInput {
+                after: Some(all[0].0.clone()),
+                ..input
+            })
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(all.len(), 3);
+        assert_eq!(emails(&rest), emails(&all[1..]));
+    }
+
+    #[tokio::test]
+    async fn should_accept_each_totp_step_once() {
+        let repo = repository().await;
+        let user = repo
+            .create(create_input("someone@example.com"))
+            .await
+            .unwrap();
+
+        let mut results = Vec::new();
+        for step in [10, 10, 9, 11] {
+            results.push(repo.consume_totp_step(user.id.clone(), step).await.ok());
+        }
+
+        assert_eq!(
+            results,
+            vec![Some(true), Some(false), Some(false), Some(true)]
+        );
+    }
+}